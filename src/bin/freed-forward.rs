@@ -0,0 +1,156 @@
+//! Receives FreeD from a PTZ and forwards it to several other hosts, because a
+//! PTZ can only send FreeD to one IP address. Optionally corrects it first.
+
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::process::exit;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+use lvc_camera_overlays::config::{Config, PtzConfig};
+use lvc_camera_overlays::freed::Transform;
+use lvc_camera_overlays::ptz::{Forwarder, Ptz};
+
+const USAGE: &str = "\
+Usage: freed-forward [options] <destination>...
+
+Listens on port 555<num> (default 1), or like a PTZ in the config of the
+overlay, and re-sends every packet to each destination, e.g. 192.168.1.20:5551
+192.168.1.30:1111. Without corrections or a camera ID, packets are sent on
+unchanged.
+
+Options:
+  --ptz <num>                  Listen on port 555<num> (default 1)
+  --config <file> --ptz <name> Receive like the PTZ called <name> in <file>:
+                               on its address, from its senders, only its
+                               camera ID, and with its corrections
+  --pan-offset <degrees>       Add to the pan angle
+  --tilt-offset <degrees>      Add to the tilt angle
+  --invert-pan                 Negate the pan angle, e.g. for ceiling mounts
+  --invert-tilt                Negate the tilt angle
+  --zoom-range <a:b>=<c:d>     Map zoom values from a..b onto c..d
+  --position <x,y,z>           Replace the position, in metres. Corrections
+                               replace those of the config.
+  --record <file>              Also write the received FreeD to a file, which
+                               the overlay can replay (see ptzs.example.toml)";
+
+fn parse_zoom_range(value: &str) -> Option<((u32, u32), (u32, u32))> {
+    let range = |r: &str| r.split_once(':').and_then(|(a, b)| Some((a.parse().ok()?, b.parse().ok()?)));
+    let (from, to) = value.split_once('=')?;
+    Some((range(from)?, range(to)?))
+}
+
+fn parse_position(value: &str) -> Option<(f32, f32, f32)> {
+    match value.split(',').map(str::parse).collect::<Result<Vec<_>, _>>().ok()?.as_slice() {
+        [x, y, z] => Some((*x, *y, *z)),
+        _ => None,
+    }
+}
+
+fn fail(message: &str) -> ! {
+    eprintln!("{message}\n\n{USAGE}");
+    exit(1)
+}
+
+/// The PTZ called `name` in the config at `path`, or else PTZ `name` of LVC
+fn ptz_config(path: Option<&str>, name: Option<&str>) -> Result<PtzConfig, String> {
+    match (path, name) {
+        (Some(path), Some(name)) => {
+            let config = Config::load(path).map_err(|e| format!("{path}: {e}"))?;
+            config.ptzs.into_iter().find(|ptz| ptz.name == name).ok_or_else(|| format!("{path} has no PTZ called {name}"))
+        }
+        (Some(..), None) => Err("--config needs --ptz <name>".to_string()),
+        (None, name) => match name.map_or(Ok(1), str::parse) {
+            Ok(num) => Ok(PtzConfig::lvc(num)),
+            Err(..) => Err("invalid value for --ptz".to_string()),
+        }
+    }
+}
+
+fn main() {
+    let mut config_path = None;
+    let mut ptz_name = None;
+    let mut destinations: Vec<SocketAddr> = Vec::new();
+    let mut transform = Transform::default();
+    let mut record = None;
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--config" => config_path = Some(args.next().unwrap_or_else(|| fail("missing value for --config"))),
+            "--ptz" => ptz_name = Some(args.next().unwrap_or_else(|| fail("missing value for --ptz"))),
+            "--pan-offset" => transform.pan_offset = args.next().and_then(|n| n.parse().ok())
+                .unwrap_or_else(|| fail("invalid or missing value for --pan-offset")),
+            "--tilt-offset" => transform.tilt_offset = args.next().and_then(|n| n.parse().ok())
+                .unwrap_or_else(|| fail("invalid or missing value for --tilt-offset")),
+            "--invert-pan" => transform.invert_pan = true,
+            "--invert-tilt" => transform.invert_tilt = true,
+            "--zoom-range" => transform.zoom_range = Some(args.next().as_deref().and_then(parse_zoom_range)
+                .unwrap_or_else(|| fail("invalid or missing value for --zoom-range"))),
+            "--position" => transform.position = Some(args.next().as_deref().and_then(parse_position)
+                .unwrap_or_else(|| fail("invalid or missing value for --position"))),
+            "--record" => record = Some(args.next().unwrap_or_else(|| fail("missing value for --record"))),
+            "-h" | "--help" => {
+                println!("{USAGE}");
+                return
+            }
+            destination => destinations.push(destination.parse()
+                .unwrap_or_else(|e| fail(&format!("invalid destination {destination}: {e}")))),
+        }
+    }
+    if destinations.is_empty() {
+        fail("no destinations given")
+    }
+
+    let running = Arc::new(AtomicBool::new(true));
+    let r = running.clone();
+    ctrlc::set_handler(move || {
+        r.store(false, Ordering::Relaxed);
+    }).expect("Error setting Ctrl-C handler");
+
+    let mut config = ptz_config(config_path.as_deref(), ptz_name.as_deref()).unwrap_or_else(|e| fail(&e));
+    if transform != Transform::default() {
+        config.transform = Some(transform);
+    }
+    if let Some(path) = record {
+        config.record = Some(PathBuf::from(path));
+    }
+
+    let forwarder = Forwarder::new(destinations.iter().copied()).expect("could not bind UDP socket");
+    let ptz = Ptz::from_config(&config).forwarding(forwarder).start(&config, running.clone())
+        .unwrap_or_else(|e| fail(&format!("could not start {}: {e}", config.name)));
+    let source = config.serial.clone().unwrap_or_else(|| ptz.address().to_string());
+    println!("Forwarding FreeD from {source} to {} destination(s).", destinations.len());
+
+    while running.load(Ordering::Relaxed) {
+        std::thread::sleep(Duration::from_millis(100));
+    }
+
+    println!("Received {}", ptz.stats());
+
+    for (destination, errors) in ptz.forwarder().unwrap().errors() {
+        println!("{destination}: {errors} failed sends");
+    }
+}
+
+#[test]
+fn test_parse() {
+    assert_eq!(parse_zoom_range("0:4095=0:16384"), Some(((0, 4095), (0, 16384))));
+    assert_eq!(parse_zoom_range("0:4095"), None);
+    assert_eq!(parse_position("1.5,-2,0.25"), Some((1.5, -2.0, 0.25)));
+    assert_eq!(parse_position("1,2"), None);
+
+    assert_eq!(ptz_config(None, None).unwrap().port, 5551);
+    assert_eq!(ptz_config(None, Some("3")).unwrap().port, 5553);
+    assert!(ptz_config(None, Some("PTZ-03")).is_err());
+    assert!(ptz_config(Some("ptzs.example.toml"), None).is_err());
+}
+
+#[test]
+fn test_ptz_from_config() {
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/ptzs.example.toml");
+    let config = ptz_config(Some(path), Some("PTZ-02")).unwrap();
+    assert_eq!((config.port, config.senders.len()), (5552, 1));
+    assert!(config.transform.is_some());
+    assert!(ptz_config(Some(path), Some("PTZ-03")).is_err());
+}
@@ -0,0 +1,311 @@
+//! Pretends to be a PTZ that sends FreeD, so the overlay can be tested without
+//! a camera on the desk.
+
+use std::f32::consts::PI;
+use std::net::{SocketAddr, UdpSocket};
+use std::process::exit;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use lvc_camera_overlays::freed::FreeD;
+
+const USAGE: &str = "\
+Usage: freed-sim [options] <trajectory>
+
+Trajectories:
+  static <pan> <tilt> <zoom> [focus]   Stand still
+  sweep [range] [period]               Pan back and forth over +/- range degrees
+                                       (default 60) while zooming in and out,
+                                       every period seconds (default 10)
+  script <file>                        Move between keyframes, one per line:
+                                       <time> <pan> <tilt> <zoom> [focus].
+                                       Loops after the last keyframe.
+  random                               Random walk
+
+Options:
+  --ptz <num>        Send to port 555<num> (default 1)
+  --target <addr>    Send to this address instead, e.g. 192.168.1.10:5551
+  --camera-id <id>   Camera ID in the messages (default 0)
+  --rate <hz>        Messages per second (default 50)
+  --jitter <ms>      Randomly vary the time between messages by up to this
+                     many milliseconds (default 0)";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+struct Pose {
+    pan: f32,
+    tilt: f32,
+    zoom: f32,
+    focus: f32,
+}
+
+enum Trajectory {
+    Static(Pose),
+    Sweep { range: f32, period: f32 },
+    /// Time in seconds since the start, and the pose at that time
+    Keyframes(Vec<(f32, Pose)>),
+    RandomWalk { pose: Pose, velocity: Pose },
+}
+
+impl Trajectory {
+    /// Highest zoom value of the LVC lens profile
+    const MAX_ZOOM: f32 = 16384.0;
+
+    /// The pose at `t` seconds since the start; `dt` is the time since the
+    /// previous pose.
+    fn pose(&mut self, t: f32, dt: f32, rng: &mut Rng) -> Pose {
+        match self {
+            Trajectory::Static(pose) => *pose,
+            Trajectory::Sweep { range, period } => {
+                let phase = 2.0 * PI * t / *period;
+                Pose {
+                    pan: *range * phase.sin(),
+                    tilt: -5.0,
+                    zoom: Self::MAX_ZOOM * (1.0 - phase.cos()) / 2.0,
+                    focus: 0.0,
+                }
+            }
+            Trajectory::Keyframes(keyframes) => {
+                let duration = keyframes.last().unwrap().0;
+                let t = if duration > 0.0 { t % duration } else { 0.0 };
+                let next = keyframes.iter().position(|(time, _)| *time > t).unwrap_or(keyframes.len() - 1);
+                if next == 0 {
+                    return keyframes[0].1
+                }
+                let (t0, a) = keyframes[next - 1];
+                let (t1, b) = keyframes[next];
+                let f = if t1 > t0 { ((t - t0) / (t1 - t0)).clamp(0.0, 1.0) } else { 1.0 };
+                Pose {
+                    pan: a.pan + (b.pan - a.pan) * f,
+                    tilt: a.tilt + (b.tilt - a.tilt) * f,
+                    zoom: a.zoom + (b.zoom - a.zoom) * f,
+                    focus: a.focus + (b.focus - a.focus) * f,
+                }
+            }
+            Trajectory::RandomWalk { pose, velocity } => {
+                // Random acceleration with some damping, so the movement looks
+                // like a camera operator rather than noise
+                velocity.pan = 0.98 * velocity.pan + 20.0 * rng.uniform() * dt;
+                velocity.tilt = 0.98 * velocity.tilt + 5.0 * rng.uniform() * dt;
+                velocity.zoom = 0.98 * velocity.zoom + 2000.0 * rng.uniform() * dt;
+                pose.pan = (pose.pan + velocity.pan * dt + 180.0).rem_euclid(360.0) - 180.0;
+                pose.tilt = (pose.tilt + velocity.tilt * dt).clamp(-90.0, 90.0);
+                pose.zoom = (pose.zoom + velocity.zoom * dt).clamp(0.0, Self::MAX_ZOOM);
+                *pose
+            }
+        }
+    }
+}
+
+/// xorshift64*: good enough for jitter and random walks, without a dependency
+struct Rng(u64);
+
+impl Rng {
+    fn new() -> Self {
+        let seed = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_nanos() as u64;
+        Rng(seed | 1)
+    }
+
+    /// A random number between -1 and 1
+    fn uniform(&mut self) -> f32 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        let value = self.0.wrapping_mul(0x2545F4914F6CDD1D) >> 40;
+        value as f32 / (1u64 << 23) as f32 - 1.0
+    }
+}
+
+/// Pan and tilt out of range would be sent, but every receiver rejects them
+fn check_angles(pan: f32, tilt: f32) -> Result<(), String> {
+    if !(-180.0..=180.0).contains(&pan) {
+        return Err(format!("pan {pan} is not between -180 and 180"))
+    }
+    if !(-90.0..=90.0).contains(&tilt) {
+        return Err(format!("tilt {tilt} is not between -90 and 90"))
+    }
+    Ok(())
+}
+
+fn parse_keyframes(script: &str) -> Result<Vec<(f32, Pose)>, String> {
+    let mut keyframes = Vec::new();
+    for (i, line) in script.lines().enumerate() {
+        let line = line.split('#').next().unwrap().trim();
+        if line.is_empty() {
+            continue
+        }
+        let values = line.split_whitespace()
+            .map(|v| v.parse::<f32>())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("line {}: {e}", i + 1))?;
+        let [time, pan, tilt, zoom, rest @ ..] = values.as_slice() else {
+            return Err(format!("line {}: expected <time> <pan> <tilt> <zoom> [focus]", i + 1))
+        };
+        if keyframes.last().is_some_and(|(last, _)| last > time) {
+            return Err(format!("line {}: keyframes must be in chronological order", i + 1))
+        }
+        check_angles(*pan, *tilt).map_err(|e| format!("line {}: {e}", i + 1))?;
+        let focus = rest.first().copied().unwrap_or(0.0);
+        keyframes.push((*time, Pose { pan: *pan, tilt: *tilt, zoom: *zoom, focus }));
+    }
+
+    if keyframes.is_empty() {
+        return Err("script contains no keyframes".to_string())
+    }
+
+    Ok(keyframes)
+}
+
+fn parse_trajectory(args: &[String]) -> Result<Trajectory, String> {
+    let numbers = |args: &[String]| args.iter()
+        .map(|v| v.parse::<f32>().map_err(|e| format!("invalid number {v}: {e}")))
+        .collect::<Result<Vec<_>, _>>();
+
+    match args.split_first() {
+        Some((name, args)) if name == "static" => {
+            let pose = match numbers(args)?.as_slice() {
+                [pan, tilt, zoom] => Pose { pan: *pan, tilt: *tilt, zoom: *zoom, focus: 0.0 },
+                [pan, tilt, zoom, focus] => Pose { pan: *pan, tilt: *tilt, zoom: *zoom, focus: *focus },
+                _ => return Err("static expects <pan> <tilt> <zoom> [focus]".to_string()),
+            };
+            check_angles(pose.pan, pose.tilt)?;
+            Ok(Trajectory::Static(pose))
+        }
+        Some((name, args)) if name == "sweep" => match numbers(args)?.as_slice() {
+            [] => Ok(Trajectory::Sweep { range: 60.0, period: 10.0 }),
+            [range] if check_angles(*range, 0.0).is_ok() => Ok(Trajectory::Sweep { range: *range, period: 10.0 }),
+            [range, period] if check_angles(*range, 0.0).is_ok() && *period > 0.0 => {
+                Ok(Trajectory::Sweep { range: *range, period: *period })
+            }
+            _ => Err("sweep expects [range] [period], with a range of at most 180 and a positive period".to_string()),
+        }
+        Some((name, [path])) if name == "script" => {
+            let script = std::fs::read_to_string(path).map_err(|e| format!("could not read {path}: {e}"))?;
+            let keyframes = parse_keyframes(&script).map_err(|e| format!("invalid script {path}: {e}"))?;
+            Ok(Trajectory::Keyframes(keyframes))
+        }
+        Some((name, [])) if name == "random" => Ok(Trajectory::RandomWalk { pose: Pose::default(), velocity: Pose::default() }),
+        Some((name, _)) => Err(format!("invalid trajectory {name}")),
+        None => Err("no trajectory given".to_string()),
+    }
+}
+
+/// Faster than any camera sends, and slow enough to still sleep in between
+const MAX_RATE: f32 = 1000.0;
+/// Far more than any network adds
+const MAX_JITTER: f32 = 1000.0;
+
+fn check_timing(rate: f32, jitter: f32) -> Result<(), String> {
+    if !(rate.is_finite() && rate > 0.0 && rate <= MAX_RATE) {
+        return Err(format!("rate must be positive, and at most {MAX_RATE} Hz"))
+    }
+    if !(0.0..=MAX_JITTER).contains(&jitter) {
+        return Err(format!("jitter must be between 0 and {MAX_JITTER} ms"))
+    }
+    Ok(())
+}
+
+fn parse<T: std::str::FromStr>(value: Option<String>, name: &str) -> T {
+    match value.as_deref().map(str::parse) {
+        Some(Ok(value)) => value,
+        _ => fail(&format!("invalid or missing value for {name}")),
+    }
+}
+
+fn fail(message: &str) -> ! {
+    eprintln!("{message}\n\n{USAGE}");
+    exit(1)
+}
+
+fn main() {
+    let mut ptz_num: u16 = 1;
+    let mut target: Option<SocketAddr> = None;
+    let mut camera_id: u8 = 0;
+    let mut rate: f32 = 50.0;
+    let mut jitter: f32 = 0.0;
+    let mut positional = Vec::new();
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--ptz" => ptz_num = parse(args.next(), "--ptz"),
+            "--target" => target = Some(parse(args.next(), "--target")),
+            "--camera-id" => camera_id = parse(args.next(), "--camera-id"),
+            "--rate" => rate = parse(args.next(), "--rate"),
+            "--jitter" => jitter = parse(args.next(), "--jitter"),
+            "-h" | "--help" => {
+                println!("{USAGE}");
+                return
+            }
+            _ => positional.push(arg),
+        }
+    }
+
+    let mut trajectory = parse_trajectory(&positional).unwrap_or_else(|e| fail(&e));
+    check_timing(rate, jitter).unwrap_or_else(|e| fail(&e));
+    let target = target.unwrap_or_else(|| {
+        let port = 5550u16.checked_add(ptz_num).unwrap_or_else(|| fail(&format!("--ptz can be at most {}", u16::MAX - 5550)));
+        SocketAddr::from(([127, 0, 0, 1], port))
+    });
+
+    let socket = UdpSocket::bind("0.0.0.0:0").expect("could not bind UDP socket");
+    let interval = 1.0 / rate;
+    let mut rng = Rng::new();
+    let start = Instant::now();
+    let mut previous = 0.0;
+    let mut next_send = start;
+
+    println!("Sending FreeD to {target} at {rate} Hz");
+
+    loop {
+        let t = start.elapsed().as_secs_f32();
+        let pose = trajectory.pose(t, t - previous, &mut rng);
+        previous = t;
+
+        let mut freed = FreeD::zero();
+        freed.camera_id = camera_id;
+        freed.pan = pose.pan;
+        freed.tilt = pose.tilt;
+        freed.zoom = pose.zoom.max(0.0) as u32;
+        freed.focus = pose.focus.max(0.0) as u32;
+
+        if let Err(e) = socket.send_to(&freed.encode(), target) {
+            eprintln!("Error sending to {target}: {e}");
+        }
+
+        // Jitter is added per message, but the average rate stays the same
+        next_send += Duration::from_secs_f32(interval);
+        let jittered = next_send + Duration::from_secs_f32(jitter / 1000.0 * rng.uniform().abs());
+        if let Some(wait) = jittered.checked_duration_since(Instant::now()) {
+            std::thread::sleep(wait);
+        }
+    }
+}
+
+#[test]
+fn test_keyframes() {
+    let keyframes = parse_keyframes("# time pan tilt zoom\n0 0 0 0\n2 10 -4 1000 # zoom in\n\n4 10 -4 1000 500\n").unwrap();
+    let mut trajectory = Trajectory::Keyframes(keyframes);
+    let mut rng = Rng::new();
+
+    assert_eq!(trajectory.pose(1.0, 0.0, &mut rng), Pose { pan: 5.0, tilt: -2.0, zoom: 500.0, focus: 0.0 });
+    assert_eq!(trajectory.pose(3.0, 0.0, &mut rng), Pose { pan: 10.0, tilt: -4.0, zoom: 1000.0, focus: 250.0 });
+    // Loops
+    assert_eq!(trajectory.pose(5.0, 0.0, &mut rng), Pose { pan: 5.0, tilt: -2.0, zoom: 500.0, focus: 0.0 });
+
+    assert!(parse_keyframes("1 2 3").is_err());
+    assert!(parse_keyframes("").is_err());
+    assert!(parse_keyframes("2 0 0 0\n1 0 0 0").is_err());
+    assert_eq!(parse_keyframes("0 0 0 0\n1 181 0 0").unwrap_err(), "line 2: pan 181 is not between -180 and 180");
+    assert_eq!(parse_keyframes("0 0 -90.5 0").unwrap_err(), "line 1: tilt -90.5 is not between -90 and 90");
+    assert!(parse_keyframes("0 nan 0 0").is_err());
+}
+
+#[test]
+fn test_timing() {
+    assert!(check_timing(50.0, 0.0).is_ok());
+    assert!(check_timing(MAX_RATE, MAX_JITTER).is_ok());
+    for rate in [0.0, -1.0, f32::NAN, f32::INFINITY, 1e30] {
+        assert!(check_timing(rate, 0.0).is_err(), "{rate}");
+    }
+    for jitter in [-1.0, f32::NAN, f32::INFINITY, 1e30] {
+        assert!(check_timing(50.0, jitter).is_err(), "{jitter}");
+    }
+}
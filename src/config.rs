@@ -0,0 +1,561 @@
+//! Which PTZs there are and where their FreeD arrives, read from a TOML file:
+//!
+//! ```toml
+//! [[ptz]]
+//! name = "PTZ-01"
+//! port = 5551
+//! # Optional: the address of the network interface to listen on
+//! bind = "192.168.1.2"
+//! # Optional: ignore FreeD from other hosts. Can also be a list.
+//! sender = "192.168.1.101"
+//! # Optional: only FreeD with this camera ID, so several PTZs can share a port
+//! camera_id = 1
+//! # Optional: ask the sender for FreeD, for cameras in polled mode
+//! poll = true
+//! # Optional: a lens profile from the lens module, or a [ptz.lens] table
+//! lens = "lvc"
+//! # Optional: when tracking is stale or lost, in milliseconds without FreeD
+//! stale_after_ms = 100
+//! lost_after_ms = 1000
+//! # Optional: how far ahead to predict movement, in milliseconds
+//! prediction_ms = 40
+//! # Optional: how far behind to render, in milliseconds or in frames
+//! delay_frames = 3
+//! frame_rate = 50
+//! # Optional: write the received FreeD to a file, see freed::recording
+//! record = "ptz-01.freed"
+//! # Optional: replay such a file in real time, instead of receiving FreeD
+//! # replay = "ptz-01.freed"
+//!
+//! # Optional: correct the FreeD before it is used
+//! [ptz.transform]
+//! pan_offset = -90
+//! invert_tilt = true
+//! zoom_range = [[0, 4095], [0, 16384]]
+//! position = [1.5, -2, 0.25]
+//!
+//! # Instead of lens = "...": raw zoom values with the magnification there,
+//! # and the raw focus values at the nearest focus and at infinity
+//! [ptz.lens]
+//! zoom = [[0, 1.0], [8192, 4.0], [16384, 12.0]]
+//! focus = [0, 4095]
+//!
+//! # Optional: filter the noise out of pan, tilt and/or zoom
+//! [ptz.filter.pan]
+//! type = "one-euro"
+//! min_cutoff = 1.0
+//! beta = 0.1
+//!
+//! [ptz.filter.zoom]
+//! type = "kalman"
+//! process_noise = 1e6
+//! measurement_noise = 25
+//!
+//! [[ptz]]
+//! name = "Jib"
+//! # Instead of port: receive FreeD from a serial port
+//! serial = "/dev/ttyUSB0"
+//! # Optional: 38400 by default, like the FreeD manual says
+//! baud = 38400
+//! ```
+
+use std::collections::HashSet;
+use std::fmt::{Display, Formatter};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use serde::{Deserialize, Deserializer};
+use crate::freed::Transform;
+use crate::lens::LensProfile;
+use crate::ptz::{Filters, History, Ptz, Timeouts};
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    #[serde(rename = "ptz", default)]
+    pub ptzs: Vec<PtzConfig>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PtzConfig {
+    /// Shown in logs and in the name of the NDI stream
+    pub name: String,
+    /// Address of the network interface to listen on. By default all of them.
+    #[serde(default = "PtzConfig::any_address")]
+    pub bind: IpAddr,
+    /// The UDP port FreeD arrives on. Required, unless it comes from a serial
+    /// port.
+    #[serde(default)]
+    pub port: u16,
+    /// Receive FreeD from this serial port instead of over UDP, e.g.
+    /// "/dev/ttyUSB0" or "COM3"
+    #[serde(default)]
+    pub serial: Option<String>,
+    /// Baud rate of the serial port. By default [`Ptz::SERIAL_BAUD_RATE`].
+    #[serde(default)]
+    pub baud: Option<u32>,
+    /// Only accept FreeD from these addresses. In the file either one
+    /// address, or a list of them.
+    #[serde(default, alias = "sender", deserialize_with = "one_or_more")]
+    pub senders: Vec<IpAddr>,
+    /// Only use FreeD with this camera ID. PTZs with different IDs can share
+    /// an address, which is useful because many send to the same port by
+    /// default. By default any ID, on an address of its own.
+    #[serde(default)]
+    pub camera_id: Option<u8>,
+    /// Ask the sender for every FreeD message, on the port of this PTZ, for
+    /// cameras in polled mode. Needs a single sender. By default the camera
+    /// streams FreeD by itself.
+    #[serde(default)]
+    pub poll: bool,
+    /// The lens profile. By default the one of the PTZs of LVC.
+    #[serde(default)]
+    pub lens: Option<LensConfig>,
+    /// After how many milliseconds without FreeD tracking is stale. By
+    /// default 100.
+    #[serde(default)]
+    pub stale_after_ms: Option<u64>,
+    /// After how many milliseconds without FreeD tracking is lost, and the
+    /// overlay is hidden. By default 1000.
+    #[serde(default)]
+    pub lost_after_ms: Option<u64>,
+    /// How many milliseconds ahead to predict the movement of the camera, to
+    /// make up for latency. By default 0.
+    #[serde(default)]
+    pub prediction_ms: u64,
+    /// How many milliseconds to delay the overlay, to line it up with video
+    /// that arrives later than FreeD
+    #[serde(default)]
+    pub delay_ms: Option<u64>,
+    /// The same, in frames. Only one of the two can be set.
+    #[serde(default)]
+    pub delay_frames: Option<u32>,
+    /// Frames per second of the video, for `delay_frames`. By default that of
+    /// the overlay, 60.
+    #[serde(default = "PtzConfig::default_frame_rate")]
+    pub frame_rate: f64,
+    /// Corrections for the FreeD of this PTZ. By default none.
+    #[serde(default)]
+    pub transform: Option<Transform>,
+    /// Noise filters for pan, tilt and zoom. By default none.
+    #[serde(default)]
+    pub filter: Filters,
+    /// Write the received FreeD to this file, see
+    /// [`crate::freed::recording`]. Not for PTZs with a camera ID.
+    #[serde(default)]
+    pub record: Option<PathBuf>,
+    /// Replay this recording in real time, instead of receiving FreeD
+    #[serde(default)]
+    pub replay: Option<PathBuf>,
+}
+
+/// A lens profile in the config: the name of a known one, see
+/// [`LensProfile::by_name`], or a profile of its own
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(untagged)]
+pub enum LensConfig {
+    Name(String),
+    Profile(LensProfile),
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(std::io::Error),
+    Parse(toml::de::Error),
+    /// The file is valid TOML, but the contents make no sense
+    Invalid(String),
+}
+
+impl Config {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, ConfigError> {
+        std::fs::read_to_string(path).map_err(ConfigError::Io)?.parse()
+    }
+
+    /// The PTZs of LVC with the given numbers, which send to port 555{num}
+    pub fn lvc(nums: impl IntoIterator<Item = u8>) -> Self {
+        Config { ptzs: nums.into_iter().map(PtzConfig::lvc).collect() }
+    }
+
+    fn validate(&self) -> Result<(), ConfigError> {
+        let mut names = HashSet::new();
+        for (i, ptz) in self.ptzs.iter().enumerate() {
+            if !names.insert(&ptz.name) {
+                return Err(ConfigError::Invalid(format!("there are several PTZs called {}", ptz.name)))
+            }
+            for other in &self.ptzs[..i] {
+                if ptz.overlaps(other) && !ptz.shares_address(other) {
+                    return Err(ConfigError::Invalid(format!("{} listens on {}, like {}", ptz.name, ptz.address(), other.name)))
+                }
+            }
+            match &ptz.serial {
+                None if ptz.port == 0 => return Err(ConfigError::Invalid(format!("{} has no port", ptz.name))),
+                None if ptz.baud.is_some() => return Err(ConfigError::Invalid(format!("{} has a baud rate, but no serial port", ptz.name))),
+                Some(..) if ptz.port != 0 || !ptz.senders.is_empty() || ptz.replay.is_some() => {
+                    return Err(ConfigError::Invalid(format!("{} receives from a serial port, so it can't have a port, senders or replay", ptz.name)))
+                }
+                Some(..) if ptz.baud == Some(0) => return Err(ConfigError::Invalid(format!("{} has a baud rate of 0", ptz.name))),
+                _ => {}
+            }
+            if ptz.record.is_some() && ptz.replay.is_some() {
+                return Err(ConfigError::Invalid(format!("{} both records and replays", ptz.name)))
+            }
+            if ptz.record.is_some() && ptz.camera_id.is_some() {
+                return Err(ConfigError::Invalid(format!("{} has a camera ID, so it can't record", ptz.name)))
+            }
+            if ptz.poll && (ptz.senders.len() != 1 || ptz.camera_id.is_some() || !ptz.listens()) {
+                return Err(ConfigError::Invalid(format!("{} can only poll a single sender, without a camera ID or replay", ptz.name)))
+            }
+            if ptz.delay_ms.is_some() && ptz.delay_frames.is_some() {
+                return Err(ConfigError::Invalid(format!("{} has both delay_ms and delay_frames", ptz.name)))
+            }
+            if !(PtzConfig::MIN_FRAME_RATE..=PtzConfig::MAX_FRAME_RATE).contains(&ptz.frame_rate) {
+                return Err(ConfigError::Invalid(format!("{} has a frame rate of {}", ptz.name, ptz.frame_rate)))
+            }
+            if ptz.prediction() > PtzConfig::MAX_LATENCY || ptz.delay() > PtzConfig::MAX_LATENCY {
+                return Err(ConfigError::Invalid(format!(
+                    "{} predicts or delays more than {} s", ptz.name, PtzConfig::MAX_LATENCY.as_secs(),
+                )))
+            }
+            for (axis, filter) in [("pan", ptz.filter.pan), ("tilt", ptz.filter.tilt), ("zoom", ptz.filter.zoom)] {
+                if let Err(problem) = filter.check() {
+                    return Err(ConfigError::Invalid(format!("the {axis} filter of {} is invalid: {problem}", ptz.name)))
+                }
+            }
+            let timeouts = ptz.timeouts();
+            if timeouts.stale > timeouts.lost {
+                return Err(ConfigError::Invalid(format!("{} becomes stale after it is lost", ptz.name)))
+            }
+            match &ptz.lens {
+                Some(LensConfig::Name(name)) if LensProfile::by_name(name).is_none() => {
+                    return Err(ConfigError::Invalid(format!("{} has an unknown lens {name}", ptz.name)))
+                }
+                Some(LensConfig::Profile(profile)) => if let Err(problem) = profile.check() {
+                    return Err(ConfigError::Invalid(format!("the lens of {} is invalid: {problem}", ptz.name)))
+                }
+                _ => {}
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl std::str::FromStr for Config {
+    type Err = ConfigError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let config: Config = toml::from_str(s).map_err(ConfigError::Parse)?;
+        config.validate()?;
+        Ok(config)
+    }
+}
+
+impl PtzConfig {
+    const LVC_BASE_PORT: u16 = 5550;
+    const MIN_FRAME_RATE: f64 = 1.0;
+    const MAX_FRAME_RATE: f64 = 1000.0;
+    /// The most a PTZ can be predicted ahead or delayed. Far more than any
+    /// video takes.
+    const MAX_LATENCY: Duration = Duration::from_secs(10);
+
+    fn any_address() -> IpAddr {
+        Ipv4Addr::UNSPECIFIED.into()
+    }
+
+    fn default_frame_rate() -> f64 {
+        60.0
+    }
+
+    /// A PTZ of LVC, which sends to port 555{num}
+    pub fn lvc(num: u8) -> Self {
+        PtzConfig {
+            name: format!("PTZ-{num:02}"),
+            bind: Self::any_address(),
+            port: Self::LVC_BASE_PORT + num as u16,
+            serial: None,
+            baud: None,
+            senders: Vec::new(),
+            camera_id: None,
+            poll: false,
+            lens: None,
+            stale_after_ms: None,
+            lost_after_ms: None,
+            prediction_ms: 0,
+            delay_ms: None,
+            delay_frames: None,
+            frame_rate: Self::default_frame_rate(),
+            transform: None,
+            filter: Filters::default(),
+            record: None,
+            replay: None,
+        }
+    }
+
+    /// The address to bind the UDP socket to
+    pub fn address(&self) -> SocketAddr {
+        SocketAddr::new(self.bind, self.port)
+    }
+
+    /// Where to send polls to, if the camera is polled
+    pub fn poll_address(&self) -> Option<SocketAddr> {
+        self.senders.first().filter(|_| self.poll).map(|&sender| SocketAddr::new(sender, self.port))
+    }
+
+    /// Whether it receives FreeD over UDP, instead of replaying it or
+    /// reading it from a serial port
+    pub fn listens(&self) -> bool {
+        self.replay.is_none() && self.serial.is_none()
+    }
+
+    pub fn baud_rate(&self) -> u32 {
+        self.baud.unwrap_or(Ptz::SERIAL_BAUD_RATE)
+    }
+
+    /// Whether both would listen on the same port of an interface. A port on
+    /// all interfaces overlaps with that port on any one of them.
+    fn overlaps(&self, other: &PtzConfig) -> bool {
+        self.listens() && other.listens() && self.port == other.port
+            && (self.bind == other.bind || self.bind.is_unspecified() || other.bind.is_unspecified())
+    }
+
+    /// Whether both can be received on a single socket, told apart by their
+    /// camera IDs
+    fn shares_address(&self, other: &PtzConfig) -> bool {
+        self.address() == other.address()
+            && matches!((self.camera_id, other.camera_id), (Some(a), Some(b)) if a != b)
+    }
+
+    pub fn timeouts(&self) -> Timeouts {
+        let default = Timeouts::default();
+        Timeouts {
+            stale: self.stale_after_ms.map_or(default.stale, Duration::from_millis),
+            lost: self.lost_after_ms.map_or(default.lost, Duration::from_millis),
+        }
+    }
+
+    pub fn prediction(&self) -> Duration {
+        Duration::from_millis(self.prediction_ms)
+    }
+
+    pub fn delay(&self) -> Duration {
+        match (self.delay_ms, self.delay_frames) {
+            (Some(ms), _) => Duration::from_millis(ms),
+            (None, Some(frames)) => Duration::try_from_secs_f64(frames as f64 / self.frame_rate).unwrap_or(Duration::MAX),
+            (None, None) => Duration::ZERO,
+        }
+    }
+
+    /// How many samples to keep, so the delay is covered even if FreeD
+    /// arrives twice as often as frames do. On top of the default, which
+    /// covers interpolation and prediction.
+    pub fn history_capacity(&self) -> usize {
+        History::DEFAULT_CAPACITY + (2.0 * self.delay().as_secs_f64() * self.frame_rate).ceil() as usize
+    }
+
+    pub fn lens(&self) -> LensProfile {
+        match &self.lens {
+            Some(LensConfig::Name(name)) => LensProfile::by_name(name).cloned().unwrap_or_default(),
+            Some(LensConfig::Profile(profile)) => profile.clone(),
+            None => LensProfile::default(),
+        }
+    }
+}
+
+fn one_or_more<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<IpAddr>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMore {
+        One(IpAddr),
+        More(Vec<IpAddr>),
+    }
+
+    Ok(match OneOrMore::deserialize(deserializer)? {
+        OneOrMore::One(ip) => vec![ip],
+        OneOrMore::More(ips) => ips,
+    })
+}
+
+impl Display for ConfigError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigError::Io(e) => write!(f, "could not read config: {e}"),
+            ConfigError::Parse(e) => write!(f, "invalid config: {e}"),
+            ConfigError::Invalid(message) => write!(f, "invalid config: {message}"),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+#[test]
+fn test_parse() {
+    let config: Config = r#"
+        [[ptz]]
+        name = "PTZ-01"
+        port = 5551
+
+        [[ptz]]
+        name = "Finish"
+        bind = "192.168.1.2"
+        port = 1111
+        senders = ["192.168.1.101", "192.168.1.102"]
+        lens = "lvc"
+        lost_after_ms = 500
+        prediction_ms = 40
+        delay_frames = 5
+        frame_rate = 50
+
+        [ptz.transform]
+        pan_offset = -90
+        invert_tilt = true
+        zoom_range = [[0, 4095], [0, 16384]]
+
+        [ptz.filter.tilt]
+        type = "kalman"
+        process_noise = 100
+        measurement_noise = 0.01
+    "#.parse().unwrap();
+
+    assert_eq!(config.ptzs[0], PtzConfig::lvc(1));
+    assert_eq!(config.ptzs[1].address(), "192.168.1.2:1111".parse().unwrap());
+    assert_eq!(config.ptzs[1].senders, ["192.168.1.101".parse::<IpAddr>().unwrap(), "192.168.1.102".parse().unwrap()]);
+    assert_eq!(config.ptzs[1].lens(), crate::lens::LVC);
+    assert_eq!(config.ptzs[1].timeouts().lost, Duration::from_millis(500));
+    assert_eq!(config.ptzs[1].prediction(), Duration::from_millis(40));
+    assert_eq!(config.ptzs[1].delay(), Duration::from_millis(100));
+    assert_eq!(config.ptzs[1].transform, Some(Transform {
+        pan_offset: -90.0,
+        invert_tilt: true,
+        zoom_range: Some(((0, 4095), (0, 16384))),
+        ..Transform::default()
+    }));
+    assert_eq!(config.ptzs[1].filter, Filters {
+        tilt: crate::ptz::Filter::Kalman { process_noise: 100.0, measurement_noise: 0.01 },
+        ..Filters::default()
+    });
+
+    let config: Config = "[[ptz]]\nname = \"a\"\nport = 1\nsender = \"10.0.0.1\"".parse().unwrap();
+    assert_eq!(config.ptzs[0].senders, ["10.0.0.1".parse::<IpAddr>().unwrap()]);
+    assert_eq!(config.ptzs[0].poll_address(), None);
+
+    let config: Config = "[[ptz]]\nname = \"a\"\nport = 1\nsender = \"10.0.0.1\"\npoll = true".parse().unwrap();
+    assert_eq!(config.ptzs[0].poll_address(), Some("10.0.0.1:1".parse().unwrap()));
+
+    // A replay doesn't take the port of a PTZ that records
+    let config: Config = r#"
+        [[ptz]]
+        name = "a"
+        port = 1
+        record = "a.freed"
+
+        [[ptz]]
+        name = "b"
+        port = 1
+        replay = "a.freed"
+    "#.parse().unwrap();
+    assert_eq!(config.ptzs[0].record, Some(PathBuf::from("a.freed")));
+    assert!(!config.ptzs[1].listens());
+
+    // A lens of its own
+    let config: Config = r#"
+        [[ptz]]
+        name = "a"
+        port = 1
+
+        [ptz.lens]
+        zoom = [[0, 1], [8192, 4.0], [16384, 12.0]]
+        focus = [4095, 0]
+    "#.parse().unwrap();
+    let lens = config.ptzs[0].lens();
+    assert_eq!((lens.name.as_ref(), lens.magnification(12288), lens.focus(0)), ("custom", 8.0, 1.0));
+
+    // Several PTZs on one port, told apart by camera ID, and another interface
+    let config: Config = r#"
+        [[ptz]]
+        name = "a"
+        port = 1
+        camera_id = 1
+
+        [[ptz]]
+        name = "b"
+        port = 1
+        camera_id = 2
+
+        [[ptz]]
+        name = "c"
+        bind = "10.0.0.1"
+        port = 2
+
+        [[ptz]]
+        name = "d"
+        bind = "10.0.0.2"
+        port = 2
+    "#.parse().unwrap();
+    assert_eq!(config.ptzs[1].camera_id, Some(2));
+
+    // A serial port, which doesn't take a UDP port
+    let config: Config = r#"
+        [[ptz]]
+        name = "a"
+        serial = "/dev/ttyUSB0"
+
+        [[ptz]]
+        name = "b"
+        serial = "/dev/ttyUSB1"
+        baud = 9600
+    "#.parse().unwrap();
+    assert!(!config.ptzs[0].listens());
+    assert_eq!((config.ptzs[0].serial.as_deref(), config.ptzs[0].baud_rate()), (Some("/dev/ttyUSB0"), 38400));
+    assert_eq!(config.ptzs[1].baud_rate(), 9600);
+}
+
+#[test]
+fn test_invalid() {
+    assert!(matches!("[[ptz]]\nname = \"a\"".parse::<Config>(), Err(ConfigError::Invalid(..))));
+    assert!(matches!("[[ptz]]\nname = \"a\"\nport = 1\nbaud = 9600".parse::<Config>(), Err(ConfigError::Invalid(..))));
+    assert!(matches!("[[ptz]]\nname = \"a\"\nport = 1\nserial = \"/dev/ttyS0\"".parse::<Config>(), Err(ConfigError::Invalid(..))));
+    assert!(matches!("[[ptz]]\nname = \"a\"\nserial = \"/dev/ttyS0\"\nsender = \"10.0.0.1\"".parse::<Config>(), Err(ConfigError::Invalid(..))));
+    assert!(matches!("[[ptz]]\nname = \"a\"\nserial = \"/dev/ttyS0\"\nreplay = \"a\"".parse::<Config>(), Err(ConfigError::Invalid(..))));
+    assert!(matches!("[[ptz]]\nname = \"a\"\nserial = \"/dev/ttyS0\"\nbaud = 0".parse::<Config>(), Err(ConfigError::Invalid(..))));
+    assert!(matches!("[[ptz]]\nname = \"a\"\nport = 1\nsender = \"localhost\"".parse::<Config>(), Err(ConfigError::Parse(..))));
+    assert!(matches!("[[ptz]]\nname = \"a\"\nport = 1\nlense = \"lvc\"".parse::<Config>(), Err(ConfigError::Parse(..))));
+    assert!(matches!("[[ptz]]\nname = \"a\"\nport = 1\nlens = \"canon\"".parse::<Config>(), Err(ConfigError::Invalid(..))));
+    assert!(matches!("[[ptz]]\nname = \"a\"\nport = 1\n[ptz.lens]\nzoom = [[10, 1], [0, 2]]\nfocus = [0, 1]".parse::<Config>(), Err(ConfigError::Invalid(..))));
+    assert!(matches!("[[ptz]]\nname = \"a\"\nport = 1\n[ptz.lens]\nzoom = [[0, 1]]".parse::<Config>(), Err(ConfigError::Parse(..))));
+    assert!(matches!("[[ptz]]\nname = \"a\"\nport = 1\ndelay_ms = 1\ndelay_frames = 1".parse::<Config>(), Err(ConfigError::Invalid(..))));
+    assert!(matches!("[[ptz]]\nname = \"a\"\nport = 1\nframe_rate = 0".parse::<Config>(), Err(ConfigError::Invalid(..))));
+    assert!(matches!("[[ptz]]\nname = \"a\"\nport = 1\nframe_rate = 1e-300\ndelay_frames = 1".parse::<Config>(), Err(ConfigError::Invalid(..))));
+    assert!(matches!("[[ptz]]\nname = \"a\"\nport = 1\ndelay_frames = 4294967295".parse::<Config>(), Err(ConfigError::Invalid(..))));
+    assert!(matches!("[[ptz]]\nname = \"a\"\nport = 1\nprediction_ms = 9223372036854775807".parse::<Config>(), Err(ConfigError::Invalid(..))));
+    assert!(matches!("[[ptz]]\nname = \"a\"\nport = 1\n[ptz.filter.pan]\ntype = \"one-euro\"".parse::<Config>(), Err(ConfigError::Parse(..))));
+    assert!(matches!("[[ptz]]\nname = \"a\"\nport = 1\n[ptz.transform]\npan = 1".parse::<Config>(), Err(ConfigError::Parse(..))));
+    assert!(matches!("[[ptz]]\nname = \"a\"\nport = 1\n[[ptz]]\nname = \"b\"\nport = 1".parse::<Config>(), Err(ConfigError::Invalid(..))));
+    assert!(matches!("[[ptz]]\nname = \"a\"\nport = 1\n[[ptz]]\nname = \"b\"\nport = 1\nbind = \"10.0.0.1\"".parse::<Config>(), Err(ConfigError::Invalid(..))));
+    assert!(matches!("[[ptz]]\nname = \"a\"\nport = 1\ncamera_id = 1\n[[ptz]]\nname = \"b\"\nport = 1\ncamera_id = 1".parse::<Config>(), Err(ConfigError::Invalid(..))));
+    assert!(matches!("[[ptz]]\nname = \"a\"\nport = 1\n[[ptz]]\nname = \"a\"\nport = 2".parse::<Config>(), Err(ConfigError::Invalid(..))));
+    assert!(matches!("[[ptz]]\nname = \"a\"\nport = 1\nrecord = \"a\"\nreplay = \"b\"".parse::<Config>(), Err(ConfigError::Invalid(..))));
+    assert!(matches!("[[ptz]]\nname = \"a\"\nport = 1\nrecord = \"a\"\ncamera_id = 1".parse::<Config>(), Err(ConfigError::Invalid(..))));
+    assert!(matches!("[[ptz]]\nname = \"a\"\nport = 1\npoll = true".parse::<Config>(), Err(ConfigError::Invalid(..))));
+    assert!(matches!("[[ptz]]\nname = \"a\"\nport = 1\npoll = true\nsenders = [\"10.0.0.1\", \"10.0.0.2\"]".parse::<Config>(), Err(ConfigError::Invalid(..))));
+    assert!(matches!("[[ptz]]\nname = \"a\"\nport = 1\npoll = true\nsender = \"10.0.0.1\"\ncamera_id = 1".parse::<Config>(), Err(ConfigError::Invalid(..))));
+    assert!(matches!("[[ptz]]\nname = \"a\"\nport = 1\npoll = true\nsender = \"10.0.0.1\"\nreplay = \"a\"".parse::<Config>(), Err(ConfigError::Invalid(..))));
+}
+
+#[test]
+fn test_invalid_filter() {
+    let invalid = |filter: &str| match format!("[[ptz]]\nname = \"a\"\nport = 1\n[ptz.filter.tilt]\n{filter}").parse::<Config>() {
+        Err(ConfigError::Invalid(message)) => message,
+        result => panic!("{filter}: {result:?}"),
+    };
+
+    assert_eq!(invalid("type = \"one-euro\"\nmin_cutoff = 0\nbeta = 0.1"), "the tilt filter of a is invalid: min_cutoff must be positive");
+    assert_eq!(invalid("type = \"one-euro\"\nmin_cutoff = nan\nbeta = 0.1"), "the tilt filter of a is invalid: min_cutoff must be positive");
+    assert_eq!(invalid("type = \"one-euro\"\nmin_cutoff = 1\nbeta = -0.1"), "the tilt filter of a is invalid: beta must be finite and not negative");
+    assert_eq!(invalid("type = \"one-euro\"\nmin_cutoff = 1\nbeta = inf"), "the tilt filter of a is invalid: beta must be finite and not negative");
+    assert_eq!(invalid("type = \"one-euro\"\nmin_cutoff = 1\nbeta = 0\nd_cutoff = 0"), "the tilt filter of a is invalid: d_cutoff must be positive");
+    assert_eq!(invalid("type = \"kalman\"\nprocess_noise = 0\nmeasurement_noise = 1"), "the tilt filter of a is invalid: process_noise must be positive");
+    assert_eq!(invalid("type = \"kalman\"\nprocess_noise = -inf\nmeasurement_noise = 1"), "the tilt filter of a is invalid: process_noise must be positive");
+    assert_eq!(invalid("type = \"kalman\"\nprocess_noise = 1\nmeasurement_noise = 0"), "the tilt filter of a is invalid: measurement_noise must be positive");
+    assert_eq!(invalid("type = \"kalman\"\nprocess_noise = 1\nmeasurement_noise = nan"), "the tilt filter of a is invalid: measurement_noise must be positive");
+}
@@ -0,0 +1,108 @@
+use super::*;
+
+/// Finds FreeD messages in a stream of bytes, for transports that don't
+/// preserve message boundaries (TCP, serial). Messages are recognised by their
+/// type byte and checksum; anything else is skipped until the next byte that
+/// could start a message.
+///
+/// Push received bytes with [`Decoder::push`], then iterate to get the
+/// messages. Every run of skipped bytes is reported as an error.
+#[derive(Debug, Default)]
+pub struct Decoder {
+    buf: Vec<u8>,
+}
+
+impl Decoder {
+    pub fn new() -> Self {
+        Decoder::default()
+    }
+
+    pub fn push(&mut self, data: &[u8]) {
+        self.buf.extend_from_slice(data);
+    }
+
+    /// Discard an incomplete message at the end of the buffer, e.g. at the end
+    /// of a datagram.
+    pub fn flush(&mut self) -> Option<ParseError> {
+        let message_type = *self.buf.first()?;
+        let expected = Message::length(message_type).unwrap_or(0);
+        let actual = self.buf.len();
+        self.buf.clear();
+
+        Some(ParseError::WrongLength { expected, actual })
+    }
+
+    /// Drop bytes up to the next byte (from `from` onwards) that could be the
+    /// start of a message.
+    fn resync(&mut self, from: usize) {
+        let next = self.buf[from..].iter()
+            .position(|b| Message::length(*b).is_some())
+            .map_or(self.buf.len(), |i| from + i);
+        self.buf.drain(..next);
+    }
+}
+
+impl Iterator for Decoder {
+    type Item = Result<Message, ParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let message_type = *self.buf.first()?;
+        let Some(length) = Message::length(message_type) else {
+            self.resync(1);
+            return Some(Err(ParseError::UnknownMessageType(message_type)))
+        };
+        if self.buf.len() < length {
+            return None // Wait for more data
+        }
+
+        let expected = checksum(&self.buf[..length - 1]);
+        let actual = self.buf[length - 1];
+        if expected != actual {
+            // Probably not the start of a message after all
+            self.resync(1);
+            return Some(Err(ParseError::ChecksumMismatch { expected, actual }))
+        }
+
+        let message = Message::try_from(&self.buf[..length]);
+        self.buf.drain(..length);
+
+        Some(message)
+    }
+}
+
+#[test]
+fn test_concatenated() {
+    let mut decoder = Decoder::new();
+    let poll = Message::poll(1);
+    let freed = FreeD { camera_id: 1, tilt: -10.0, ..FreeD::zero() };
+    decoder.push(&freed.encode());
+    decoder.push(&poll.encode());
+    decoder.push(&freed.encode());
+
+    assert_eq!(decoder.next(), Some(Ok(Message::Position(FreeD { camera_id: 1, tilt: -10.0, ..FreeD::zero() }))));
+    assert_eq!(decoder.next(), Some(Ok(poll)));
+    assert_eq!(decoder.next(), Some(Ok(Message::Position(freed))));
+    assert_eq!(decoder.next(), None);
+    assert_eq!(decoder.flush(), None);
+}
+
+#[test]
+fn test_resync() {
+    let mut decoder = Decoder::new();
+    let bytes = FreeD { pan: 90.0, ..FreeD::zero() }.encode();
+    decoder.push(&[0x00, 0x12]);
+    decoder.push(&bytes[..10]); // Interrupted message
+    decoder.push(&bytes[..20]);
+    decoder.push(&bytes[20..]);
+
+    let results: Vec<_> = decoder.by_ref().collect();
+    assert_eq!(results.len(), 3);
+    assert_eq!(results[0], Err(ParseError::UnknownMessageType(0x00)));
+    assert!(matches!(results[1], Err(ParseError::ChecksumMismatch { .. })));
+    assert_eq!(results[2], Ok(Message::Position(FreeD { pan: 90.0, ..FreeD::zero() })));
+
+    decoder.push(&bytes[..12]);
+    assert_eq!(decoder.next(), None);
+    assert_eq!(decoder.flush(), Some(ParseError::WrongLength { expected: 29, actual: 12 }));
+    assert_eq!(decoder.next(), None);
+}
@@ -0,0 +1,35 @@
+use std::fmt::{Display, Formatter};
+
+/// Reasons a FreeD message can be rejected
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ParseError {
+    /// The message is not as long as its message type requires
+    WrongLength { expected: usize, actual: usize },
+    /// The first byte is not a message type we know of
+    UnknownMessageType(u8),
+    /// A D0 message contains a command we don't know of
+    UnknownCommand(u8),
+    /// The checksum byte does not match the contents of the message
+    ChecksumMismatch { expected: u8, actual: u8 },
+    /// A value is outside the range the protocol allows for it
+    OutOfRange { field: &'static str, value: f32 },
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParseError::WrongLength { expected, actual } =>
+                write!(f, "wrong message length: expected {expected} bytes, got {actual}"),
+            ParseError::UnknownMessageType(message_type) =>
+                write!(f, "unknown message type 0x{message_type:02X}"),
+            ParseError::UnknownCommand(command) =>
+                write!(f, "unknown command 0x{command:02X}"),
+            ParseError::ChecksumMismatch { expected, actual } =>
+                write!(f, "checksum mismatch: expected 0x{expected:02X}, got 0x{actual:02X}"),
+            ParseError::OutOfRange { field, value } =>
+                write!(f, "{field} out of range: {value}"),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
@@ -0,0 +1,301 @@
+use super::*;
+
+/// Camera ID that every FreeD unit responds to, regardless of its own ID
+pub const BROADCAST_CAMERA_ID: u8 = 0xFF;
+
+/// All FreeD messages we understand. See appendix A of the free-d installation
+/// manual for the full protocol.
+#[derive(Debug, PartialEq)]
+pub enum Message {
+    /// D0: poll a FreeD unit for data or send it a command
+    Command { camera_id: u8, command: Command },
+    /// D1: camera position/orientation data
+    Position(FreeD),
+    /// D2: system status of a FreeD unit
+    SystemStatus(SystemStatus),
+    /// DA: calibration data of the camera lens
+    CameraCalibration(CameraCalibration),
+}
+
+/// Commands that can be sent in a D0 message
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Command {
+    StopStreamMode,
+    StartStreamMode,
+    StopFreezeMode,
+    StartFreezeMode,
+    /// Poll for a position update (D1). Also stops stream mode.
+    PollPosition,
+    RequestSystemStatus,
+    RequestSystemParameters,
+    RequestFirstTargetData,
+    RequestNextTargetData,
+    RequestFirstImagePoint,
+    RequestNextImagePoint,
+    RequestNextEepromData,
+    RequestCameraCalibration,
+    RequestDiagnosticMode,
+}
+
+/// Contents of a D2 message
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SystemStatus {
+    pub camera_id: u8,
+    /// Setting of the switches on the FreeD unit
+    pub switch_settings: u8,
+    /// Bit 0: video input present, 1: video input OK, 2: serial data input
+    /// present, 3: data freeze mode, 4: too few targets, 5: RMS error high,
+    /// 6: DSP alert, 7: fault.
+    pub led_indications: u8,
+    /// Zero when the system is normal, otherwise an error code
+    pub system_status: u8,
+    /// Firmware and software versions, in BCD: 0x12 means version 1.2
+    pub cpu_version: u8,
+    pub pld_version: u8,
+    pub dsp_version: u8,
+    /// Negative values indicate an error, positive values are the number of
+    /// iterations needed to compute the camera position.
+    pub dsp_status: i8,
+    pub targets_seen: u8,
+    pub targets_identified: u8,
+    pub targets_used: u8,
+    /// RMS error in pixels
+    pub rms_error: f32,
+}
+
+/// Contents of a DA message. The manual does not specify the units, so the
+/// values are the raw 24-bit signed numbers.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CameraCalibration {
+    pub camera_id: u8,
+    pub lens_centre: (i32, i32),
+    pub lens_scale: (i32, i32),
+    /// Radial lens distortion: square term and fourth power term
+    pub lens_distortion: (i32, i32),
+    /// Offset from the auxiliary camera to the studio camera (X, Y, Z)
+    pub offset: (i32, i32, i32),
+}
+
+impl Message {
+    /// The total length in bytes of a message of this type, including message
+    /// type and checksum.
+    pub const fn length(message_type: u8) -> Option<usize> {
+        match message_type {
+            0xD0 => Some(4),
+            0xD1 => Some(29),
+            0xD2 => Some(16),
+            0xDA => Some(30),
+            _ => None,
+        }
+    }
+
+    pub fn message_type(&self) -> u8 {
+        match self {
+            Message::Command { .. } => 0xD0,
+            Message::Position(..) => 0xD1,
+            Message::SystemStatus(..) => 0xD2,
+            Message::CameraCalibration(..) => 0xDA,
+        }
+    }
+
+    pub fn camera_id(&self) -> u8 {
+        match self {
+            Message::Command { camera_id, .. } => *camera_id,
+            Message::Position(freed) => freed.camera_id,
+            Message::SystemStatus(status) => status.camera_id,
+            Message::CameraCalibration(calibration) => calibration.camera_id,
+        }
+    }
+
+    /// A D0 message that polls the camera(s) for a position update.
+    pub fn poll(camera_id: u8) -> Self {
+        Message::Command { camera_id, command: Command::PollPosition }
+    }
+
+    /// Encode the message, including the checksum.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut data = match self {
+            Message::Command { camera_id, command } => vec![0xD0, *camera_id, (*command).into(), 0],
+            Message::Position(freed) => return freed.encode().to_vec(),
+            Message::SystemStatus(status) => {
+                let mut data = vec![
+                    0xD2,
+                    status.camera_id,
+                    status.switch_settings,
+                    status.led_indications,
+                    status.system_status,
+                    status.cpu_version,
+                    status.pld_version,
+                    status.dsp_version,
+                    status.dsp_status as u8,
+                    status.targets_seen,
+                    status.targets_identified,
+                    status.targets_used,
+                ];
+                data.extend(encode_float(status.rms_error * ANGLE_DIVISOR));
+                data.push(0);
+                data
+            }
+            Message::CameraCalibration(calibration) => {
+                let mut data = vec![0xDA, calibration.camera_id];
+                for value in [
+                    calibration.lens_centre.0,
+                    calibration.lens_centre.1,
+                    calibration.lens_scale.0,
+                    calibration.lens_scale.1,
+                    calibration.lens_distortion.0,
+                    calibration.lens_distortion.1,
+                    calibration.offset.0,
+                    calibration.offset.1,
+                    calibration.offset.2,
+                ] {
+                    data.extend(encode_float(value as f32));
+                }
+                data.push(0);
+                data
+            }
+        };
+
+        let last = data.len() - 1;
+        data[last] = checksum(&data[..last]);
+        data
+    }
+}
+
+impl TryFrom<&[u8]> for Message {
+    type Error = ParseError;
+
+    fn try_from(data: &[u8]) -> Result<Self, Self::Error> {
+        let Some(&message_type) = data.first() else {
+            return Err(ParseError::WrongLength { expected: 1, actual: 0 })
+        };
+        let Some(expected) = Message::length(message_type) else {
+            return Err(ParseError::UnknownMessageType(message_type))
+        };
+        if data.len() != expected {
+            return Err(ParseError::WrongLength { expected, actual: data.len() })
+        }
+        let expected = checksum(&data[..data.len() - 1]);
+        if expected != data[data.len() - 1] {
+            return Err(ParseError::ChecksumMismatch { expected, actual: data[data.len() - 1] })
+        }
+
+        Ok(match message_type {
+            0xD0 => Message::Command { camera_id: data[1], command: Command::try_from(data[2])? },
+            0xD1 => Message::Position(FreeD::try_from(data)?),
+            0xD2 => Message::SystemStatus(SystemStatus {
+                camera_id: data[1],
+                switch_settings: data[2],
+                led_indications: data[3],
+                system_status: data[4],
+                cpu_version: data[5],
+                pld_version: data[6],
+                dsp_version: data[7],
+                dsp_status: data[8] as i8,
+                targets_seen: data[9],
+                targets_identified: data[10],
+                targets_used: data[11],
+                rms_error: decode_float(&data[12..15]) / ANGLE_DIVISOR,
+            }),
+            0xDA => {
+                let value = |i: usize| decode_float(&data[2 + 3 * i..5 + 3 * i]) as i32;
+                Message::CameraCalibration(CameraCalibration {
+                    camera_id: data[1],
+                    lens_centre: (value(0), value(1)),
+                    lens_scale: (value(2), value(3)),
+                    lens_distortion: (value(4), value(5)),
+                    offset: (value(6), value(7), value(8)),
+                })
+            }
+            _ => unreachable!("message length is known"),
+        })
+    }
+}
+
+impl From<Command> for u8 {
+    fn from(command: Command) -> Self {
+        match command {
+            Command::StopStreamMode => 0x00,
+            Command::StartStreamMode => 0x01,
+            Command::StopFreezeMode => 0x02,
+            Command::StartFreezeMode => 0x03,
+            Command::PollPosition => 0xD1,
+            Command::RequestSystemStatus => 0xD2,
+            Command::RequestSystemParameters => 0xD3,
+            Command::RequestFirstTargetData => 0xD4,
+            Command::RequestNextTargetData => 0xD5,
+            Command::RequestFirstImagePoint => 0xD6,
+            Command::RequestNextImagePoint => 0xD7,
+            Command::RequestNextEepromData => 0xD8,
+            Command::RequestCameraCalibration => 0xDA,
+            Command::RequestDiagnosticMode => 0xDB,
+        }
+    }
+}
+
+impl TryFrom<u8> for Command {
+    type Error = ParseError;
+
+    fn try_from(command: u8) -> Result<Self, Self::Error> {
+        Ok(match command {
+            0x00 => Command::StopStreamMode,
+            0x01 => Command::StartStreamMode,
+            0x02 => Command::StopFreezeMode,
+            0x03 => Command::StartFreezeMode,
+            0xD1 => Command::PollPosition,
+            0xD2 => Command::RequestSystemStatus,
+            0xD3 => Command::RequestSystemParameters,
+            0xD4 => Command::RequestFirstTargetData,
+            0xD5 => Command::RequestNextTargetData,
+            0xD6 => Command::RequestFirstImagePoint,
+            0xD7 => Command::RequestNextImagePoint,
+            0xD8 => Command::RequestNextEepromData,
+            0xDA => Command::RequestCameraCalibration,
+            0xDB => Command::RequestDiagnosticMode,
+            _ => return Err(ParseError::UnknownCommand(command)),
+        })
+    }
+}
+
+#[test]
+fn test_poll() {
+    let poll = Message::poll(BROADCAST_CAMERA_ID).encode();
+
+    assert_eq!(poll, [0xD0, 0xFF, 0xD1, 0xA0]);
+    assert_eq!(Message::try_from(poll.as_slice()), Ok(Message::poll(BROADCAST_CAMERA_ID)));
+}
+
+#[test]
+fn test_round_trip() {
+    let messages = [
+        Message::Command { camera_id: 2, command: Command::StartStreamMode },
+        Message::Position(FreeD { camera_id: 1, pan: 45.5, zoom: 1234, ..FreeD::zero() }),
+        Message::SystemStatus(SystemStatus {
+            camera_id: 3,
+            switch_settings: 0x8F,
+            led_indications: 0b0000_0111,
+            system_status: 0,
+            cpu_version: 0x14,
+            pld_version: 0x10,
+            dsp_version: 0x12,
+            dsp_status: -1,
+            targets_seen: 12,
+            targets_identified: 10,
+            targets_used: 9,
+            rms_error: 0.75,
+        }),
+        Message::CameraCalibration(CameraCalibration {
+            camera_id: 4,
+            lens_centre: (1000, -1000),
+            lens_scale: (0x7FFFFF, -0x800000),
+            lens_distortion: (5, -5),
+            offset: (1, 2, 3),
+        }),
+    ];
+
+    for message in messages {
+        let bytes = message.encode();
+        assert_eq!(Some(bytes.len()), Message::length(message.message_type()));
+        assert_eq!(Message::try_from(bytes.as_slice()), Ok(message));
+    }
+}
@@ -0,0 +1,297 @@
+pub use decoder::Decoder;
+pub use error::ParseError;
+pub use message::*;
+pub use transform::Transform;
+pub(crate) use transform::wrap_angle;
+
+mod decoder;
+mod error;
+mod message;
+pub mod pcap;
+pub mod recording;
+mod transform;
+
+const ANGLE_DIVISOR: f32 = 32768.0;
+/// Positions are sent in 1/64 mm
+const POSITION_DIVISOR: f32 = 64_000.0;
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FreeD {
+    /// Identifies the camera the message belongs to. The ID 0xFF is reserved
+    /// as a broadcast address in messages sent to a FreeD unit.
+    pub camera_id: u8,
+    /// The Camera Pan Angle is defined as the angle between the Y-axis and the
+    /// projection of the optical axis of the camera onto the horizontal (XY)
+    /// plane. A zero value corresponds to the camera looking in the positive Y
+    /// direction and a positive value indicates a pan to the right (ie, the
+    /// camera rotates clockwise when viewed from above).
+    pub pan: f32,
+    /// The Camera Tilt Angle is defined as the angle between the optical axis
+    /// of the camera and the horizontal (XY) plane. A positive value indicates
+    /// an upwards tilt. If the pan and tilt angles are both zero, the camera is
+    /// looking in the direction of the positive Y axis.
+    pub tilt: f32,
+    /// The Camera Roll angle is usually zero for PTZs.
+    pub roll: f32,
+    /// Camera position (X, Y, Z) in metres, in the coordinate system of the
+    /// studio: X and Y are horizontal and Z points up. Many PTZs don't know
+    /// where they are and send zeros.
+    pub position: (f32, f32, f32),
+    /// The Camera Zoom is defined as the vertical angle of view of the camera;
+    /// ie, the vertical angle subtended at the camera lens by the top and
+    /// bottom edges of the active picture. The value is expressed in arbitrary
+    /// units related to the rotation of the 'zoom ring' on the camera lens.
+    pub zoom: u32,
+    /// The Camera Focus is defined as the distance between the camera lens and
+    /// an object at which the object will be in sharp focus. The value is
+    /// expressed in arbitrary units related to the rotation of the 'focus ring'
+    /// on the camera lens.
+    pub focus: u32,
+    /// Spare / user defined 16 bits. Not used by the protocol itself, but some
+    /// manufacturers put e.g. an iris value in here.
+    pub spare: u16,
+}
+
+impl FreeD {
+    pub const fn zero() -> Self {
+        FreeD {
+            camera_id: 0,
+            pan: 0.0,
+            tilt: 0.0,
+            roll: 0.0,
+            position: (0.0, 0.0, 0.0),
+            zoom: 0,
+            focus: 0,
+            spare: 0,
+        }
+    }
+
+    /// The values a fraction `f` of the way from `self` to `other`. Angles
+    /// take the short way around, so from 170° to -170° passes 180°. The
+    /// camera ID and spare bits are those of the nearest of the two.
+    pub fn interpolate(&self, other: &FreeD, f: f32) -> FreeD {
+        let angle = |a: f32, b: f32| transform::wrap_angle(a + transform::wrap_angle(b - a) * f);
+        let linear = |a: f32, b: f32| a + (b - a) * f;
+        let unsigned = |a: u32, b: u32| linear(a as f32, b as f32).round() as u32;
+        let nearest = if f < 0.5 { self } else { other };
+
+        FreeD {
+            camera_id: nearest.camera_id,
+            pan: angle(self.pan, other.pan),
+            tilt: linear(self.tilt, other.tilt),
+            roll: angle(self.roll, other.roll),
+            position: (
+                linear(self.position.0, other.position.0),
+                linear(self.position.1, other.position.1),
+                linear(self.position.2, other.position.2),
+            ),
+            zoom: unsigned(self.zoom, other.zoom),
+            focus: unsigned(self.focus, other.focus),
+            spare: nearest.spare,
+        }
+    }
+
+    /// Encode as a D1 (camera position/orientation data) message, including
+    /// the checksum. Values that don't fit in their 24 bits are saturated.
+    pub fn encode(&self) -> [u8; 29] {
+        let mut data = [0u8; 29];
+        data[0] = 0xD1;
+        data[1] = self.camera_id;
+        data[2..5].copy_from_slice(&encode_float(self.pan * ANGLE_DIVISOR));
+        data[5..8].copy_from_slice(&encode_float(self.tilt * ANGLE_DIVISOR));
+        data[8..11].copy_from_slice(&encode_float(self.roll * ANGLE_DIVISOR));
+        data[11..14].copy_from_slice(&encode_float(self.position.0 * POSITION_DIVISOR));
+        data[14..17].copy_from_slice(&encode_float(self.position.1 * POSITION_DIVISOR));
+        data[17..20].copy_from_slice(&encode_float(self.position.2 * POSITION_DIVISOR));
+        data[20..23].copy_from_slice(&encode_unsigned(self.zoom));
+        data[23..26].copy_from_slice(&encode_unsigned(self.focus));
+        data[26..28].copy_from_slice(&self.spare.to_be_bytes());
+        data[28] = checksum(&data[..28]);
+
+        data
+    }
+}
+
+impl From<&FreeD> for [u8; 29] {
+    fn from(freed: &FreeD) -> Self {
+        freed.encode()
+    }
+}
+
+impl TryFrom<&[u8]> for FreeD {
+    type Error = ParseError;
+
+    fn try_from(data: &[u8]) -> Result<Self, Self::Error> {
+        match <&[u8] as TryInto<&[u8; 29]>>::try_into(data) {
+            Ok(data) => FreeD::try_from(data),
+            Err(..) if data.first().is_some_and(|t| *t != 0xD1) => Err(ParseError::UnknownMessageType(data[0])),
+            Err(..) => Err(ParseError::WrongLength { expected: 29, actual: data.len() }),
+        }
+    }
+}
+
+impl TryFrom<&[u8; 29]> for FreeD {
+    type Error = ParseError;
+
+    fn try_from(data: &[u8; 29]) -> Result<Self, Self::Error> {
+        if data[0] != 0xD1 {
+            return Err(ParseError::UnknownMessageType(data[0]))
+        }
+        let expected = checksum(&data[..28]);
+        if expected != data[28] {
+            return Err(ParseError::ChecksumMismatch { expected, actual: data[28] })
+        }
+
+        Ok(FreeD {
+            camera_id: data[1],
+            pan: decode_angle(&data[2..5], "pan", 180.0)?,
+            tilt: decode_angle(&data[5..8], "tilt", 90.0)?,
+            roll: decode_angle(&data[8..11], "roll", 180.0)?,
+            position: (
+                decode_float(&data[11..14]) / POSITION_DIVISOR,
+                decode_float(&data[14..17]) / POSITION_DIVISOR,
+                decode_float(&data[17..20]) / POSITION_DIVISOR,
+            ),
+            zoom: u32::from_be_bytes([0, data[20], data[21], data[22]]),
+            focus: u32::from_be_bytes([0, data[23], data[24], data[25]]),
+            spare: u16::from_be_bytes([data[26], data[27]]),
+        })
+    }
+}
+
+/// The checksum is calculated by subtracting (modulo 256) each byte of the
+/// message, including the message type, from 40 (hex). For a message that
+/// includes its checksum byte, the result is zero.
+fn checksum(data: &[u8]) -> u8 {
+    data.iter().fold(0x40, |acc, el| acc.overflowing_sub(*el).0)
+}
+
+fn decode_float(bytes: &[u8]) -> f32 {
+    i32::from_be_bytes([bytes[0], bytes[1], bytes[2], 0]) as f32 / 256.0
+}
+
+/// Decode an angle in degrees, which must lie within `-limit..=limit`.
+fn decode_angle(bytes: &[u8], field: &'static str, limit: f32) -> Result<f32, ParseError> {
+    let value = decode_float(bytes) / ANGLE_DIVISOR;
+    if value.abs() > limit {
+        return Err(ParseError::OutOfRange { field, value })
+    }
+
+    Ok(value)
+}
+
+/// Inverse of [`decode_float`]: a 24-bit two's complement number.
+fn encode_float(value: f32) -> [u8; 3] {
+    const MAX: f32 = 0x7FFFFF as f32;
+    let bytes = (value.round().clamp(-MAX - 1.0, MAX) as i32).to_be_bytes();
+    [bytes[1], bytes[2], bytes[3]]
+}
+
+fn encode_unsigned(value: u32) -> [u8; 3] {
+    let bytes = value.min(0xFFFFFF).to_be_bytes();
+    [bytes[1], bytes[2], bytes[3]]
+}
+
+#[test]
+fn test_example() {
+    let bytes1: [u8; 29] = [0xd1, 0x00, 0xdf, 0x78, 0xaa, 0x00, 0x47, 0xef,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x40, 0x00, 0x00, 0x15, 0x01, 0xd1, 0xff, 0x12];
+    let bytes2: [u8; 29] = [0xd1, 0x00, 0x21, 0x98, 0xd8, 0xff, 0xeb, 0x94,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x28, 0xc6, 0x00, 0x13, 0xb9, 0xd1, 0xff, 0xd6];
+
+    let freed_result1 = FreeD::try_from(&bytes1);
+    let freed_result2 = FreeD::try_from(&bytes2);
+
+    assert!(freed_result1.is_ok());
+    assert!(freed_result2.is_ok());
+
+    assert_eq!(freed_result1.unwrap(), FreeD {
+        camera_id: 0,
+        pan: -65.05731,
+        tilt: 0.5619812,
+        roll: 0.0,
+        position: (0.0, 0.0, 0.0),
+        zoom: 16384,
+        focus: 5377,
+        spare: 0xD1FF,
+    });
+
+    assert_eq!(freed_result2.unwrap(), FreeD {
+        camera_id: 0,
+        pan: 67.19409,
+        tilt: -0.1595459,
+        roll: 0.0,
+        position: (0.0, 0.0, 0.0),
+        zoom: 10438,
+        focus: 5049,
+        spare: 0xD1FF,
+    });
+}
+
+#[test]
+fn test_encode_round_trip() {
+    let bytes1: [u8; 29] = [0xd1, 0x00, 0xdf, 0x78, 0xaa, 0x00, 0x47, 0xef,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x40, 0x00, 0x00, 0x15, 0x01, 0xd1, 0xff, 0x12];
+    let bytes2: [u8; 29] = [0xd1, 0x00, 0x21, 0x98, 0xd8, 0xff, 0xeb, 0x94,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x28, 0xc6, 0x00, 0x13, 0xb9, 0xd1, 0xff, 0xd6];
+
+    assert_eq!(FreeD::try_from(&bytes1).unwrap().encode(), bytes1);
+    assert_eq!(FreeD::try_from(&bytes2).unwrap().encode(), bytes2);
+}
+
+#[test]
+fn test_encode() {
+    let freed = FreeD {
+        camera_id: 3,
+        pan: -180.0,
+        tilt: 90.0,
+        roll: 1.5,
+        position: (1.0, -2.5005, 0.000015625),
+        zoom: 0xABCDEF,
+        focus: 0x123456,
+        spare: 0xBEEF,
+    };
+    let bytes = freed.encode();
+
+    assert_eq!(&bytes[..5], &[0xD1, 0x03, 0xA6, 0x00, 0x00]);
+    assert_eq!(&bytes[5..8], &[0x2D, 0x00, 0x00]);
+    // 1 m is 64000 units of 1/64 mm
+    assert_eq!(&bytes[11..14], &[0x00, 0xFA, 0x00]);
+    assert_eq!(checksum(&bytes), 0);
+    assert_eq!(FreeD::try_from(&bytes).unwrap(), freed);
+}
+
+#[test]
+fn test_interpolate() {
+    let a = FreeD { camera_id: 1, pan: 170.0, tilt: 10.0, position: (1.0, 0.0, 0.0), zoom: 100, ..FreeD::zero() };
+    let b = FreeD { camera_id: 2, pan: -170.0, tilt: 20.0, position: (2.0, 0.0, 0.0), zoom: 200, ..FreeD::zero() };
+
+    assert_eq!(a.interpolate(&b, 0.0), a);
+    assert_eq!(a.interpolate(&b, 1.0), b);
+    let halfway = a.interpolate(&b, 0.5);
+    assert_eq!((halfway.pan.abs(), halfway.tilt, halfway.position.0, halfway.zoom), (180.0, 15.0, 1.5, 150));
+    assert_eq!(a.interpolate(&b, 0.25).pan, 175.0);
+    assert_eq!(a.interpolate(&b, 0.75).pan, -175.0);
+    assert_eq!(a.interpolate(&b, 0.25).camera_id, 1);
+}
+
+#[test]
+fn test_errors() {
+    let bytes = FreeD { pan: 12.5, ..FreeD::zero() }.encode();
+
+    assert_eq!(FreeD::try_from(&bytes[..28]), Err(ParseError::WrongLength { expected: 29, actual: 28 }));
+    assert_eq!(FreeD::try_from(&[0xD2; 29]), Err(ParseError::UnknownMessageType(0xD2)));
+
+    let mut corrupted = bytes;
+    corrupted[3] = corrupted[3].wrapping_add(1);
+    assert_eq!(FreeD::try_from(&corrupted), Err(ParseError::ChecksumMismatch { expected: bytes[28].wrapping_sub(1), actual: bytes[28] }));
+
+    let mut out_of_range = bytes;
+    out_of_range[5] = 0x2E; // Tilt of 92°
+    out_of_range[28] = checksum(&out_of_range[..28]);
+    assert_eq!(FreeD::try_from(&out_of_range), Err(ParseError::OutOfRange { field: "tilt", value: 92.0 }));
+}
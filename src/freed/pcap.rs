@@ -0,0 +1,460 @@
+//! Reads FreeD from Wireshark/tcpdump captures, in both the classic pcap and
+//! the pcapng format. Only UDP over IPv4 or IPv6 is considered, on Ethernet
+//! (with or without VLAN tags), Linux cooked, loopback or raw IP captures.
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{BufReader, ErrorKind, Read};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use super::*;
+use super::recording::Record;
+
+/// Which UDP datagrams to look at. `None` matches anything.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Filter {
+    /// Destination port, e.g. 5551
+    pub port: Option<u16>,
+    /// IP address of the camera
+    pub source: Option<IpAddr>,
+}
+
+/// Pulls the FreeD position messages out of a capture, as [`Record`]s with
+/// the capture timestamp.
+pub struct PcapReader<R: Read = BufReader<File>> {
+    reader: R,
+    filter: Filter,
+    format: Format,
+    /// Messages from a datagram that contained more than one
+    pending: VecDeque<Record>,
+}
+
+enum Format {
+    Pcap { big_endian: bool, nanos: bool, link_type: u32 },
+    PcapNg { big_endian: bool, interfaces: Vec<Interface> },
+}
+
+struct Interface {
+    link_type: u32,
+    resolution: Resolution,
+}
+
+/// Timestamp unit of a pcapng interface
+#[derive(Clone, Copy)]
+enum Resolution {
+    /// 10^-n seconds
+    Decimal(u32),
+    /// 2^-n seconds
+    Binary(u32),
+}
+
+const PCAPNG_SECTION_HEADER: u32 = 0x0A0D0D0A;
+const PCAPNG_INTERFACE_DESCRIPTION: u32 = 1;
+const PCAPNG_ENHANCED_PACKET: u32 = 6;
+
+const LINK_TYPE_NULL: u32 = 0;
+const LINK_TYPE_ETHERNET: u32 = 1;
+const LINK_TYPE_RAW: u32 = 101;
+const LINK_TYPE_LOOP: u32 = 108;
+const LINK_TYPE_LINUX_SLL: u32 = 113;
+const LINK_TYPE_LINUX_SLL2: u32 = 276;
+
+/// The largest packet or pcapng block that is read, so a corrupt length
+/// doesn't allocate without limit. Far more than a FreeD datagram needs.
+const MAX_LENGTH: usize = 256 * 1024;
+
+impl PcapReader {
+    pub fn open(path: impl AsRef<Path>, filter: Filter) -> std::io::Result<Self> {
+        PcapReader::new(BufReader::new(File::open(path)?), filter)
+    }
+}
+
+impl<R: Read> PcapReader<R> {
+    pub fn new(mut reader: R, filter: Filter) -> std::io::Result<Self> {
+        let mut magic = [0u8; 4];
+        reader.read_exact(&mut magic)?;
+
+        let format = match magic {
+            [0xA1, 0xB2, 0xC3, 0xD4] | [0xA1, 0xB2, 0x3C, 0x4D] | [0xD4, 0xC3, 0xB2, 0xA1] | [0x4D, 0x3C, 0xB2, 0xA1] => {
+                let big_endian = magic[0] == 0xA1;
+                let nanos = magic[2] == 0x3C || magic[1] == 0x3C;
+                let mut header = [0u8; 20];
+                reader.read_exact(&mut header)?;
+                let link_type = read_u32(&header[16..20], big_endian) & 0xFFFF;
+                Format::Pcap { big_endian, nanos, link_type }
+            }
+            [0x0A, 0x0D, 0x0D, 0x0A] => {
+                // The byte order magic comes after the block length
+                let mut header = [0u8; 8];
+                reader.read_exact(&mut header)?;
+                let big_endian = match header[4..8] {
+                    [0x1A, 0x2B, 0x3C, 0x4D] => true,
+                    [0x4D, 0x3C, 0x2B, 0x1A] => false,
+                    _ => return Err(invalid_data("invalid pcapng byte order magic")),
+                };
+                skip(&mut reader, block_body_length(&header[0..4], big_endian)?)?;
+                Format::PcapNg { big_endian, interfaces: Vec::new() }
+            }
+            _ => return Err(invalid_data("not a pcap or pcapng file")),
+        };
+
+        Ok(PcapReader { reader, filter, format, pending: VecDeque::new() })
+    }
+
+    /// Read the next captured frame with its timestamp and link type, or
+    /// `None` at the end of the capture.
+    fn read_frame(&mut self) -> std::io::Result<Option<(SystemTime, u32, Vec<u8>)>> {
+        match &mut self.format {
+            Format::Pcap { big_endian, nanos, link_type } => {
+                let mut header = [0u8; 16];
+                if !read_or_eof(&mut self.reader, &mut header)? {
+                    return Ok(None)
+                }
+                let seconds = read_u32(&header[0..4], *big_endian) as u64;
+                let fraction = read_u32(&header[4..8], *big_endian);
+                let fraction = match nanos {
+                    true => fraction,
+                    false => fraction.checked_mul(1000).ok_or_else(|| invalid_data("invalid pcap timestamp"))?,
+                };
+                let length = read_u32(&header[8..12], *big_endian) as usize;
+                if length > MAX_LENGTH {
+                    return Err(invalid_data("pcap packet too long"))
+                }
+                let mut data = vec![0u8; length];
+                self.reader.read_exact(&mut data)?;
+
+                Ok(Some((UNIX_EPOCH + Duration::new(seconds, fraction), *link_type, data)))
+            }
+            Format::PcapNg { big_endian, interfaces } => loop {
+                let mut header = [0u8; 8];
+                if !read_or_eof(&mut self.reader, &mut header)? {
+                    return Ok(None)
+                }
+                if read_u32(&header[0..4], true) == PCAPNG_SECTION_HEADER {
+                    // New section, possibly with a different byte order
+                    let mut magic = [0u8; 4];
+                    self.reader.read_exact(&mut magic)?;
+                    *big_endian = magic == [0x1A, 0x2B, 0x3C, 0x4D];
+                    interfaces.clear();
+                    skip(&mut self.reader, block_body_length(&header[4..8], *big_endian)?)?;
+                    continue;
+                }
+
+                let block_type = read_u32(&header[0..4], *big_endian);
+                let mut body = vec![0u8; block_body_length(&header[4..8], *big_endian)?];
+                self.reader.read_exact(&mut body)?;
+                skip(&mut self.reader, 4)?; // Trailing block length
+
+                match block_type {
+                    PCAPNG_INTERFACE_DESCRIPTION if body.len() >= 8 => {
+                        interfaces.push(Interface {
+                            link_type: read_u16(&body[0..2], *big_endian) as u32,
+                            resolution: timestamp_resolution(&body[8..], *big_endian),
+                        });
+                    }
+                    PCAPNG_ENHANCED_PACKET if body.len() >= 20 => {
+                        let interface = interfaces.get(read_u32(&body[0..4], *big_endian) as usize)
+                            .ok_or_else(|| invalid_data("packet from undefined pcapng interface"))?;
+                        let timestamp = (read_u32(&body[4..8], *big_endian) as u64) << 32
+                            | read_u32(&body[8..12], *big_endian) as u64;
+                        let captured = read_u32(&body[12..16], *big_endian) as usize;
+                        let data = 20usize.checked_add(captured).and_then(|end| body.get(20..end))
+                            .ok_or_else(|| invalid_data("invalid pcapng packet length"))?;
+
+                        return Ok(Some((to_system_time(timestamp, interface.resolution)?, interface.link_type, data.to_vec())))
+                    }
+                    _ => {} // Statistics, name resolution etc.
+                }
+            }
+        }
+    }
+}
+
+impl<R: Read> Iterator for PcapReader<R> {
+    type Item = std::io::Result<Record>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.pending.is_empty() {
+            let (time, link_type, frame) = match self.read_frame() {
+                Ok(Some(frame)) => frame,
+                Ok(None) => return None,
+                Err(e) => return Some(Err(e)),
+            };
+            let Some((source, port, payload)) = udp_payload(link_type, &frame) else {
+                continue
+            };
+            if self.filter.port.is_some_and(|p| p != port) || self.filter.source.is_some_and(|ip| ip != source.ip()) {
+                continue
+            }
+
+            // Other UDP traffic on the same port is not our problem
+            let mut decoder = Decoder::new();
+            decoder.push(payload);
+            for message in decoder {
+                if let Ok(Message::Position(freed)) = message {
+                    self.pending.push_back(Record { time, source: Some(source), freed });
+                }
+            }
+        }
+
+        self.pending.pop_front().map(Ok)
+    }
+}
+
+/// Source address, destination port and payload of a UDP datagram in a frame
+fn udp_payload(link_type: u32, frame: &[u8]) -> Option<(SocketAddr, u16, &[u8])> {
+    let (ethertype, packet) = match link_type {
+        LINK_TYPE_ETHERNET => {
+            let mut offset = 12;
+            let mut ethertype = read_u16(frame.get(offset..offset + 2)?, true);
+            // 802.1Q and 802.1ad VLAN tags
+            while ethertype == 0x8100 || ethertype == 0x88A8 {
+                offset += 4;
+                ethertype = read_u16(frame.get(offset..offset + 2)?, true);
+            }
+            (ethertype, frame.get(offset + 2..)?)
+        }
+        LINK_TYPE_NULL | LINK_TYPE_LOOP => {
+            // Address family, in the byte order of the capturing machine
+            let family = read_u32(frame.get(0..4)?, false).min(read_u32(frame.get(0..4)?, true));
+            let ethertype = match family {
+                2 => 0x0800,
+                24 | 28 | 30 => 0x86DD,
+                _ => return None,
+            };
+            (ethertype, frame.get(4..)?)
+        }
+        LINK_TYPE_RAW => match frame.first()? >> 4 {
+            4 => (0x0800, frame),
+            6 => (0x86DD, frame),
+            _ => return None,
+        }
+        LINK_TYPE_LINUX_SLL => (read_u16(frame.get(14..16)?, true), frame.get(16..)?),
+        LINK_TYPE_LINUX_SLL2 => (read_u16(frame.get(0..2)?, true), frame.get(20..)?),
+        _ => return None,
+    };
+
+    let (source, datagram) = match ethertype {
+        0x0800 => {
+            let header_length = (*packet.first()? as usize & 0x0F) * 4;
+            let total_length = read_u16(packet.get(2..4)?, true) as usize;
+            let fragment = read_u16(packet.get(6..8)?, true);
+            // Protocol must be UDP, and fragments are not reassembled
+            if *packet.get(9)? != 17 || fragment & 0x3FFF != 0 {
+                return None
+            }
+            let source: [u8; 4] = packet.get(12..16)?.try_into().ok()?;
+            (IpAddr::V4(Ipv4Addr::from(source)), packet.get(header_length..total_length)?)
+        }
+        0x86DD => {
+            let payload_length = read_u16(packet.get(4..6)?, true) as usize;
+            // Extension headers are not supported
+            if *packet.get(6)? != 17 {
+                return None
+            }
+            let source: [u8; 16] = packet.get(8..24)?.try_into().ok()?;
+            (IpAddr::V6(Ipv6Addr::from(source)), packet.get(40..40 + payload_length)?)
+        }
+        _ => return None,
+    };
+
+    let source_port = read_u16(datagram.get(0..2)?, true);
+    let destination_port = read_u16(datagram.get(2..4)?, true);
+    let length = read_u16(datagram.get(4..6)?, true) as usize;
+
+    Some((SocketAddr::new(source, source_port), destination_port, datagram.get(8..length)?))
+}
+
+/// Find the if_tsresol option of an interface description block
+fn timestamp_resolution(mut options: &[u8], big_endian: bool) -> Resolution {
+    while options.len() >= 4 {
+        let code = read_u16(&options[0..2], big_endian);
+        let length = read_u16(&options[2..4], big_endian) as usize;
+        if code == 9 && length == 1 && options.len() > 4 {
+            let value = options[4];
+            return if value & 0x80 == 0 {
+                Resolution::Decimal(value as u32)
+            } else {
+                Resolution::Binary((value & 0x7F) as u32)
+            }
+        }
+        if code == 0 {
+            break
+        }
+        options = options.get(4 + length.next_multiple_of(4)..).unwrap_or_default();
+    }
+
+    Resolution::Decimal(6)
+}
+
+fn to_system_time(timestamp: u64, resolution: Resolution) -> std::io::Result<SystemTime> {
+    let nanos = match resolution {
+        Resolution::Decimal(n) => {
+            let units = 10u128.checked_pow(n).ok_or_else(|| invalid_data("invalid pcapng timestamp resolution"))?;
+            timestamp as u128 * 1_000_000_000 / units
+        }
+        Resolution::Binary(n) => (timestamp as u128 * 1_000_000_000) >> n,
+    };
+
+    Ok(UNIX_EPOCH + Duration::from_nanos(nanos as u64))
+}
+
+/// The length of a pcapng block from its length field, less the 12 bytes of
+/// its type, its length and the trailing copy of its length
+fn block_body_length(field: &[u8], big_endian: bool) -> std::io::Result<usize> {
+    match read_u32(field, big_endian) as usize {
+        length if length > MAX_LENGTH => Err(invalid_data("pcapng block too long")),
+        length => length.checked_sub(12).ok_or_else(|| invalid_data("invalid pcapng block length")),
+    }
+}
+
+fn read_u16(bytes: &[u8], big_endian: bool) -> u16 {
+    let bytes = [bytes[0], bytes[1]];
+    if big_endian { u16::from_be_bytes(bytes) } else { u16::from_le_bytes(bytes) }
+}
+
+fn read_u32(bytes: &[u8], big_endian: bool) -> u32 {
+    let bytes = [bytes[0], bytes[1], bytes[2], bytes[3]];
+    if big_endian { u32::from_be_bytes(bytes) } else { u32::from_le_bytes(bytes) }
+}
+
+/// Like `read_exact`, but returns `false` if the reader is at its end.
+fn read_or_eof(reader: &mut impl Read, buf: &mut [u8]) -> std::io::Result<bool> {
+    match reader.read_exact(buf) {
+        Ok(()) => Ok(true),
+        Err(e) if e.kind() == ErrorKind::UnexpectedEof => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+fn skip(reader: &mut impl Read, amount: usize) -> std::io::Result<()> {
+    std::io::copy(&mut reader.take(amount as u64), &mut std::io::sink())?;
+    Ok(())
+}
+
+fn invalid_data(message: &str) -> std::io::Error {
+    std::io::Error::new(ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+fn ethernet_frame(source: [u8; 4], destination_port: u16, payload: &[u8]) -> Vec<u8> {
+    let udp_length = 8 + payload.len() as u16;
+    let mut frame = vec![0xFF; 12];
+    frame.extend([0x81, 0x00, 0x00, 0x0A]); // VLAN 10
+    frame.extend([0x08, 0x00]);
+    frame.extend([0x45, 0x00]);
+    frame.extend((20 + udp_length).to_be_bytes());
+    frame.extend([0x00, 0x00, 0x40, 0x00, 0x40, 17, 0x00, 0x00]);
+    frame.extend(source);
+    frame.extend([192, 168, 1, 10]);
+    frame.extend(50000u16.to_be_bytes());
+    frame.extend(destination_port.to_be_bytes());
+    frame.extend(udp_length.to_be_bytes());
+    frame.extend([0x00, 0x00]);
+    frame.extend(payload);
+    frame
+}
+
+#[test]
+fn test_pcap() {
+    let freed = FreeD { camera_id: 1, pan: 33.0, ..FreeD::zero() };
+    let frames = [
+        (1_000, ethernet_frame([192, 168, 1, 21], 5551, &freed.encode())),
+        (1_001, ethernet_frame([192, 168, 1, 22], 5551, &freed.encode())),
+        (1_002, ethernet_frame([192, 168, 1, 21], 53, &[1, 2, 3])),
+    ];
+
+    let mut file = vec![0xD4, 0xC3, 0xB2, 0xA1, 2, 0, 4, 0];
+    file.extend([0; 8]);
+    file.extend(65535u32.to_le_bytes());
+    file.extend(LINK_TYPE_ETHERNET.to_le_bytes());
+    for (seconds, frame) in &frames {
+        file.extend((*seconds as u32).to_le_bytes());
+        file.extend(250_000u32.to_le_bytes());
+        file.extend((frame.len() as u32).to_le_bytes());
+        file.extend((frame.len() as u32).to_le_bytes());
+        file.extend(frame);
+    }
+
+    let filter = Filter { port: Some(5551), source: Some("192.168.1.21".parse().unwrap()) };
+    let records: Vec<_> = PcapReader::new(file.as_slice(), filter).unwrap().collect::<Result<_, _>>().unwrap();
+    assert_eq!(records, [Record {
+        time: UNIX_EPOCH + Duration::from_millis(1_000_250),
+        source: Some("192.168.1.21:50000".parse().unwrap()),
+        freed: freed.clone(),
+    }]);
+
+    let records = PcapReader::new(file.as_slice(), Filter::default()).unwrap().count();
+    assert_eq!(records, 2);
+}
+
+#[test]
+fn test_pcapng() {
+    let freed = FreeD { camera_id: 2, tilt: 5.0, ..FreeD::zero() };
+    let mut payload = freed.encode().to_vec();
+    payload.extend(freed.encode());
+    let frame = ethernet_frame([10, 0, 0, 2], 5552, &payload);
+
+    let block = |block_type: u32, body: &[u8]| {
+        let length = 12 + body.len() as u32;
+        let mut block = block_type.to_be_bytes().to_vec();
+        block.extend(length.to_be_bytes());
+        block.extend(body);
+        block.extend(length.to_be_bytes());
+        block
+    };
+
+    let mut section = vec![0x1A, 0x2B, 0x3C, 0x4D, 0, 1, 0, 0];
+    section.extend([0xFF; 8]);
+    let mut interface = vec![0, 1, 0, 0, 0, 0, 0xFF, 0xFF];
+    interface.extend([0, 9, 0, 1, 9, 0, 0, 0, 0, 0, 0, 0]); // Nanosecond resolution
+    let timestamp: u64 = 1_700_000_000_123_456_789;
+    let mut packet = vec![0; 4];
+    packet.extend(((timestamp >> 32) as u32).to_be_bytes());
+    packet.extend((timestamp as u32).to_be_bytes());
+    packet.extend((frame.len() as u32).to_be_bytes());
+    packet.extend((frame.len() as u32).to_be_bytes());
+    packet.extend(&frame);
+    packet.resize(packet.len().next_multiple_of(4), 0);
+
+    let mut file = block(PCAPNG_SECTION_HEADER, &section);
+    file.extend(block(PCAPNG_INTERFACE_DESCRIPTION, &interface));
+    file.extend(block(5, &[0; 8])); // Interface statistics
+    file.extend(block(PCAPNG_ENHANCED_PACKET, &packet));
+
+    let records: Vec<_> = PcapReader::new(file.as_slice(), Filter::default()).unwrap().collect::<Result<_, _>>().unwrap();
+    assert_eq!(records.len(), 2);
+    assert_eq!(records[1].time, UNIX_EPOCH + Duration::from_nanos(timestamp));
+    assert_eq!(records[1].source, Some("10.0.0.2:50000".parse().unwrap()));
+    assert_eq!(records[1].freed, freed);
+}
+
+#[test]
+fn test_malformed() {
+    let header = |fraction: u32, length: u32| {
+        let mut file = vec![0xD4, 0xC3, 0xB2, 0xA1, 2, 0, 4, 0];
+        file.extend([0; 8]);
+        file.extend(65535u32.to_le_bytes());
+        file.extend(LINK_TYPE_ETHERNET.to_le_bytes());
+        file.extend(1_000u32.to_le_bytes());
+        file.extend(fraction.to_le_bytes());
+        file.extend(length.to_le_bytes());
+        file.extend(length.to_le_bytes());
+        file
+    };
+    let first = |file: &[u8]| PcapReader::new(file, Filter::default()).and_then(|mut reader| reader.next().unwrap());
+
+    // Errors instead of overflowing or allocating whatever the file says
+    for file in [header(u32::MAX, 0), header(0, u32::MAX)] {
+        assert_eq!(first(&file).unwrap_err().kind(), ErrorKind::InvalidData);
+    }
+
+    let mut file = vec![0x0A, 0x0D, 0x0D, 0x0A];
+    file.extend(u32::MAX.to_be_bytes());
+    file.extend([0x1A, 0x2B, 0x3C, 0x4D]);
+    assert_eq!(first(&file).unwrap_err().kind(), ErrorKind::InvalidData);
+
+    // A timestamp resolution of 10^-100 s
+    assert!(to_system_time(1, Resolution::Decimal(100)).is_err());
+    assert_eq!(to_system_time(1, Resolution::Decimal(9)).unwrap(), UNIX_EPOCH + Duration::from_nanos(1));
+}
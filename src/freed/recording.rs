@@ -0,0 +1,178 @@
+//! A compact file format for recorded FreeD traffic. The file starts with
+//! [`MAGIC`], followed by one entry per received D1 message:
+//!
+//! ```text
+//! timestamp   8 bytes   microseconds since the UNIX epoch, big endian
+//! family      1 byte    0: no source address, 4: IPv4, 6: IPv6
+//! address     0/4/16    source IP address
+//! port        0/2       source port, big endian
+//! message     29 bytes  the D1 message
+//! ```
+//!
+//! The message is not stored byte for byte as received, but encoded again from
+//! what was decoded, so e.g. its checksum is recomputed. Only a packet capture
+//! keeps the datagrams as they were, see [`super::pcap`].
+use std::fs::File;
+use std::io::{BufReader, BufWriter, ErrorKind, Read, Write};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use super::*;
+
+/// Identifies a FreeD recording, including the version of the format
+pub const MAGIC: &[u8; 8] = b"FREED\x00\x00\x01";
+
+/// A received FreeD message with its metadata
+#[derive(Debug, Clone, PartialEq)]
+pub struct Record {
+    pub time: SystemTime,
+    /// Where the message came from, if it was received over the network
+    pub source: Option<SocketAddr>,
+    pub freed: FreeD,
+}
+
+#[derive(Debug)]
+pub struct Recorder<W: Write = BufWriter<File>> {
+    writer: W,
+}
+
+impl Recorder {
+    /// Create (or truncate) a recording file.
+    pub fn create(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        Recorder::new(BufWriter::new(File::create(path)?))
+    }
+}
+
+impl<W: Write> Recorder<W> {
+    pub fn new(mut writer: W) -> std::io::Result<Self> {
+        writer.write_all(MAGIC)?;
+        Ok(Recorder { writer })
+    }
+
+    pub fn write(&mut self, time: SystemTime, source: Option<SocketAddr>, freed: &FreeD) -> std::io::Result<()> {
+        let micros = time.duration_since(UNIX_EPOCH).unwrap_or_default().as_micros() as u64;
+        self.writer.write_all(&micros.to_be_bytes())?;
+        match source {
+            None => self.writer.write_all(&[0])?,
+            Some(SocketAddr::V4(address)) => {
+                self.writer.write_all(&[4])?;
+                self.writer.write_all(&address.ip().octets())?;
+                self.writer.write_all(&address.port().to_be_bytes())?;
+            }
+            Some(SocketAddr::V6(address)) => {
+                self.writer.write_all(&[6])?;
+                self.writer.write_all(&address.ip().octets())?;
+                self.writer.write_all(&address.port().to_be_bytes())?;
+            }
+        }
+        self.writer.write_all(&freed.encode())
+    }
+
+    pub fn flush(&mut self) -> std::io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+/// Reads the records from a recording, in order
+pub struct Reader<R: Read = BufReader<File>> {
+    reader: R,
+}
+
+impl Reader {
+    pub fn open(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        Reader::new(BufReader::new(File::open(path)?))
+    }
+}
+
+impl<R: Read> Reader<R> {
+    pub fn new(mut reader: R) -> std::io::Result<Self> {
+        let mut magic = [0u8; 8];
+        reader.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(std::io::Error::new(ErrorKind::InvalidData, "not a FreeD recording"))
+        }
+
+        Ok(Reader { reader })
+    }
+
+    fn read_record(&mut self) -> std::io::Result<Option<Record>> {
+        let mut timestamp = [0u8; 8];
+        match self.reader.read_exact(&mut timestamp) {
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+            result => result?,
+        }
+        let time = UNIX_EPOCH + Duration::from_micros(u64::from_be_bytes(timestamp));
+
+        let mut family = [0u8; 1];
+        self.reader.read_exact(&mut family)?;
+        let ip = match family[0] {
+            0 => None,
+            4 => {
+                let mut octets = [0u8; 4];
+                self.reader.read_exact(&mut octets)?;
+                Some(IpAddr::V4(Ipv4Addr::from(octets)))
+            }
+            6 => {
+                let mut octets = [0u8; 16];
+                self.reader.read_exact(&mut octets)?;
+                Some(IpAddr::V6(Ipv6Addr::from(octets)))
+            }
+            _ => return Err(std::io::Error::new(ErrorKind::InvalidData, "invalid address family")),
+        };
+        let source = match ip {
+            Some(ip) => {
+                let mut port = [0u8; 2];
+                self.reader.read_exact(&mut port)?;
+                Some(SocketAddr::new(ip, u16::from_be_bytes(port)))
+            }
+            None => None,
+        };
+
+        let mut message = [0u8; 29];
+        self.reader.read_exact(&mut message)?;
+        let freed = FreeD::try_from(&message)
+            .map_err(|e| std::io::Error::new(ErrorKind::InvalidData, e))?;
+
+        Ok(Some(Record { time, source, freed }))
+    }
+}
+
+impl<R: Read> Iterator for Reader<R> {
+    type Item = std::io::Result<Record>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read_record().transpose()
+    }
+}
+
+#[test]
+fn test_round_trip() {
+    let records = [
+        Record {
+            time: UNIX_EPOCH + Duration::from_micros(1_700_000_000_123_456),
+            source: Some("192.168.1.21:5551".parse().unwrap()),
+            freed: FreeD { camera_id: 1, pan: 12.0, zoom: 4096, ..FreeD::zero() },
+        },
+        Record {
+            time: UNIX_EPOCH + Duration::from_micros(1_700_000_000_140_123),
+            source: Some("[fe80::1]:5551".parse().unwrap()),
+            freed: FreeD { camera_id: 1, pan: 12.5, zoom: 4100, ..FreeD::zero() },
+        },
+        Record {
+            time: UNIX_EPOCH + Duration::from_micros(1_700_000_000_160_000),
+            source: None,
+            freed: FreeD { camera_id: 2, tilt: -3.0, ..FreeD::zero() },
+        },
+    ];
+
+    let mut recorder = Recorder::new(Vec::new()).unwrap();
+    for record in &records {
+        recorder.write(record.time, record.source, &record.freed).unwrap();
+    }
+
+    let reader = Reader::new(recorder.writer.as_slice()).unwrap();
+    let read: Vec<_> = reader.collect::<Result<_, _>>().unwrap();
+    assert_eq!(read, records);
+
+    assert!(Reader::new(&b"not a recording"[..]).is_err());
+}
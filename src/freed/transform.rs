@@ -0,0 +1,90 @@
+use serde::Deserialize;
+use super::*;
+
+/// Corrections for a camera, applied to its FreeD before it is used or sent on,
+/// so that the overlay and other systems receiving the corrected stream agree.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Transform {
+    /// Degrees added to the pan angle, to make 0° point in the reference
+    /// direction
+    pub pan_offset: f32,
+    /// Degrees added to the tilt angle
+    pub tilt_offset: f32,
+    /// Negate the pan angle, e.g. for heads mounted upside down
+    pub invert_pan: bool,
+    /// Negate the tilt angle, e.g. for heads mounted upside down
+    pub invert_tilt: bool,
+    /// Linearly map zoom values from the first range onto the second, e.g.
+    /// from the range of the camera to the range the overlay expects
+    pub zoom_range: Option<((u32, u32), (u32, u32))>,
+    /// Replace the position by this one (X, Y, Z in metres), for heads that
+    /// don't know where they are
+    pub position: Option<(f32, f32, f32)>,
+}
+
+impl Transform {
+    pub fn apply(&self, freed: &FreeD) -> FreeD {
+        let mut pan = if self.invert_pan { -freed.pan } else { freed.pan };
+        pan = wrap_angle(pan + self.pan_offset);
+        let mut tilt = if self.invert_tilt { -freed.tilt } else { freed.tilt };
+        tilt = (tilt + self.tilt_offset).clamp(-90.0, 90.0);
+
+        let zoom = match self.zoom_range {
+            Some(((from_min, from_max), (to_min, to_max))) if from_max > from_min => {
+                let f = (freed.zoom.clamp(from_min, from_max) - from_min) as f64 / (from_max - from_min) as f64;
+                (to_min as f64 + f * (to_max as f64 - to_min as f64)).round() as u32
+            }
+            _ => freed.zoom,
+        };
+
+        let position = self.position.unwrap_or(freed.position);
+
+        FreeD { pan, tilt, zoom, position, ..freed.clone() }
+    }
+}
+
+/// Wrap an angle in degrees to -180..=180
+pub(crate) fn wrap_angle(angle: f32) -> f32 {
+    let wrapped = (angle + 180.0).rem_euclid(360.0) - 180.0;
+    if wrapped == -180.0 && angle > 0.0 { 180.0 } else { wrapped }
+}
+
+#[test]
+fn test_transform() {
+    let freed = FreeD { camera_id: 2, pan: 170.0, tilt: 10.0, zoom: 1000, focus: 77, ..FreeD::zero() };
+
+    assert_eq!(Transform::default().apply(&freed), freed);
+
+    let transform = Transform {
+        pan_offset: 20.0,
+        invert_tilt: true,
+        zoom_range: Some(((0, 2000), (0, 16384))),
+        position: Some((1.5, -2.0, 0.25)),
+        ..Transform::default()
+    };
+    assert_eq!(transform.apply(&freed), FreeD {
+        camera_id: 2,
+        pan: -170.0,
+        tilt: -10.0,
+        position: (1.5, -2.0, 0.25),
+        zoom: 8192,
+        focus: 77,
+        ..FreeD::zero()
+    });
+
+    let inverted = Transform { invert_pan: true, zoom_range: Some(((500, 1500), (4000, 3000))), ..Transform::default() };
+    let transformed = inverted.apply(&freed);
+    assert_eq!((transformed.pan, transformed.zoom), (-170.0, 3500));
+    // Re-encoding keeps the corrected values
+    assert_eq!(FreeD::try_from(&transformed.encode()), Ok(transformed));
+}
+
+#[test]
+fn test_wrap_angle() {
+    assert_eq!(wrap_angle(190.0), -170.0);
+    assert_eq!(wrap_angle(-190.0), 170.0);
+    assert_eq!(wrap_angle(180.0), 180.0);
+    assert_eq!(wrap_angle(-180.0), -180.0);
+    assert_eq!(wrap_angle(540.0), 180.0);
+}
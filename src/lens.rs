@@ -0,0 +1,135 @@
+//! How the raw zoom and focus values in FreeD relate to the lens of a camera
+//! model. Every vendor uses its own encoder range, so the overlay needs to know
+//! which camera it is looking through. Cameras without a profile here can get
+//! one in the config.
+
+use std::borrow::Cow;
+use serde::Deserialize;
+
+/// The zoom and focus encoders of a camera model.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LensProfile {
+    #[serde(default = "LensProfile::custom_name")]
+    pub name: Cow<'static, str>,
+    /// Raw zoom values, in increasing order, and the magnification at that
+    /// value relative to the widest zoom, i.e. the ratio of the focal lengths.
+    /// Linear in between, and clamped outside of the first and last value.
+    pub zoom: Cow<'static, [(u32, f32)]>,
+    /// Raw focus values at the nearest focus and at infinity. Either can be
+    /// the larger one.
+    pub focus: (u32, u32),
+}
+
+/// The PTZs of LVC, measured
+pub const LVC: LensProfile = LensProfile {
+    name: Cow::Borrowed("lvc"),
+    zoom: Cow::Borrowed(&[(0, 1.0), (4096, 1.6), (16384, 5.35)]),
+    focus: (0, 4095),
+};
+
+/// Profiles are only added once they are measured, and are named after the
+/// camera model.
+pub static PROFILES: [LensProfile; 1] = [LVC];
+
+impl LensProfile {
+    fn custom_name() -> Cow<'static, str> {
+        Cow::Borrowed("custom")
+    }
+
+    /// Look up a profile by its name, ignoring case
+    pub fn by_name(name: &str) -> Option<&'static LensProfile> {
+        PROFILES.iter().find(|profile| profile.name.eq_ignore_ascii_case(name))
+    }
+
+    /// The lowest and highest raw zoom value
+    pub fn zoom_range(&self) -> (u32, u32) {
+        (self.zoom[0].0, self.zoom[self.zoom.len() - 1].0)
+    }
+
+    /// The magnification relative to the widest zoom. Values outside of the
+    /// range of the encoder are clamped, because they don't mean anything
+    /// else.
+    pub fn magnification(&self, zoom: u32) -> f32 {
+        let next = self.zoom.iter().position(|(raw, _)| *raw > zoom).unwrap_or(self.zoom.len());
+        if next == 0 {
+            return self.zoom[0].1
+        } else if next == self.zoom.len() {
+            return self.zoom[next - 1].1
+        }
+        let (z0, m0) = self.zoom[next - 1];
+        let (z1, m1) = self.zoom[next];
+        m0 + (m1 - m0) * (zoom - z0) as f32 / (z1 - z0) as f32
+    }
+
+    /// Where focus is, from 0 (nearest) to 1 (infinity)
+    pub fn focus(&self, focus: u32) -> f32 {
+        let (near, far) = self.focus;
+        ((focus as f32 - near as f32) / (far as f32 - near as f32)).clamp(0.0, 1.0)
+    }
+
+    /// What is wrong with the profile, if anything
+    pub fn check(&self) -> Result<(), &'static str> {
+        if self.zoom.is_empty() {
+            Err("zoom needs at least one value")
+        } else if !self.zoom.windows(2).all(|w| w[0].0 < w[1].0) {
+            Err("zoom values must increase")
+        } else if !self.zoom.iter().all(|(_, magnification)| magnification.is_finite() && *magnification > 0.0) {
+            Err("magnifications must be positive")
+        } else if self.focus.0 == self.focus.1 {
+            Err("focus needs two different values")
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl Default for LensProfile {
+    fn default() -> Self {
+        LVC
+    }
+}
+
+/// Zoom starting above 0, and focus decreasing towards infinity
+#[cfg(test)]
+const OFFSET: LensProfile = LensProfile {
+    name: Cow::Borrowed("offset"),
+    zoom: Cow::Borrowed(&[(0x555, 1.0), (0xFFF, 20.0)]),
+    focus: (0xF000, 0x1000),
+};
+
+#[test]
+fn test_magnification() {
+    assert_eq!(LVC.magnification(0), 1.0);
+    assert_eq!(LVC.magnification(2048), 1.3);
+    assert_eq!(LVC.magnification(4096), 1.6);
+    assert_eq!(LVC.magnification(16384), 5.35);
+    // Out of range doesn't panic
+    assert_eq!(LVC.magnification(u32::MAX), 5.35);
+    assert_eq!(OFFSET.magnification(0), 1.0);
+    assert_eq!(OFFSET.zoom_range(), (0x555, 0xFFF));
+
+    for profile in &PROFILES {
+        assert_eq!(profile.check(), Ok(()), "{}", profile.name);
+    }
+}
+
+#[test]
+fn test_focus_and_lookup() {
+    assert_eq!(OFFSET.focus(0xF000), 0.0);
+    assert_eq!(OFFSET.focus(0x1000), 1.0);
+    assert_eq!(OFFSET.focus(0), 1.0);
+    assert_eq!(LensProfile::by_name("LVC"), Some(&LVC));
+    assert_eq!(LensProfile::by_name("canon"), None);
+}
+
+#[test]
+fn test_check() {
+    assert_eq!(OFFSET.check(), Ok(()));
+    let invalid = |zoom: &'static [(u32, f32)], focus| LensProfile { zoom: Cow::Borrowed(zoom), focus, ..LVC }.check();
+    assert!(invalid(&[], (0, 1)).is_err());
+    assert!(invalid(&[(10, 1.0), (10, 2.0)], (0, 1)).is_err());
+    assert!(invalid(&[(0, 1.0), (10, f32::NAN)], (0, 1)).is_err());
+    assert!(invalid(&[(0, 0.0)], (0, 1)).is_err());
+    assert!(invalid(&[(0, 1.0)], (5, 5)).is_err());
+}
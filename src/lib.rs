@@ -0,0 +1,7 @@
+//! Receiving, decoding and producing FreeD camera tracking data. The NDI
+//! overlay itself lives in the main binary.
+
+pub mod config;
+pub mod freed;
+pub mod lens;
+pub mod ptz;
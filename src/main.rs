@@ -1,18 +1,32 @@
+use std::collections::HashMap;
 use std::sync::Arc;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::thread;
 use std::time::{Duration, SystemTime};
 use nalgebra::Vector3;
-use crate::ptz::Ptz;
+use lvc_camera_overlays::config::Config;
+use lvc_camera_overlays::lens::LensProfile;
+use lvc_camera_overlays::ptz::{Ptz, PtzGroup, Statistics, TrackingState};
 use crate::renderer::camera::Camera;
 use crate::renderer::frame::Frame;
 
-mod freed;
 mod ndi;
-mod ptz;
 mod renderer;
 
 fn main() {
+    // Without a config file, only PTZ 1 of LVC
+    let config = match std::env::args().nth(1) {
+        Some(path) => Config::load(&path).unwrap_or_else(|e| {
+            eprintln!("{path}: {e}");
+            std::process::exit(1)
+        }),
+        None => Config::lvc([1]),
+    };
+    if config.ptzs.is_empty() {
+        eprintln!("No PTZs configured");
+        std::process::exit(1)
+    }
+
     let running = Arc::new(AtomicBool::new(true));
     let r = running.clone();
 
@@ -20,38 +34,87 @@ fn main() {
         r.store(false, Ordering::Relaxed);
     }).expect("Error setting Ctrl-C handler");
 
+    // Before initializing NDI, so a port that is in use stops us right away.
+    // PTZs with a camera ID are received together with the others on their
+    // address.
+    let mut groups = HashMap::new();
+    let ptzs: Vec<_> = config.ptzs.iter().map(|ptz_config| {
+        let ptz = Ptz::from_config(ptz_config);
+        let (ptz, socket) = match ptz_config.camera_id {
+            Some(camera_id) if ptz_config.listens() => {
+                let address = ptz_config.address();
+                let group = groups.remove(&address).unwrap_or_else(|| PtzGroup::new(address));
+                let socket = group.statistics();
+                groups.insert(address, group.add(camera_id, &ptz));
+                (ptz, Some(socket))
+            }
+            _ => {
+                let ptz = ptz.start(ptz_config, running.clone()).unwrap_or_else(|e| {
+                    eprintln!("Could not start {}: {e}", ptz_config.name);
+                    std::process::exit(1)
+                });
+                match (&ptz_config.replay, &ptz_config.serial) {
+                    (Some(path), _) => println!("Replaying {} for {}.", path.display(), ptz.name()),
+                    (None, Some(path)) => println!("FreeD listener for {} started on {path}.", ptz.name()),
+                    (None, None) => println!("FreeD listener for {} started on {}.", ptz.name(), ptz.address()),
+                }
+                if let Some(address) = ptz_config.poll_address() {
+                    println!("Polling {address} for FreeD.");
+                }
+                (ptz, None)
+            }
+        };
+        (ptz, ptz_config.lens(), socket)
+    }).collect();
+    let _listeners: Vec<_> = groups.into_iter().map(|(address, group)| {
+        let listener = group.start_listening(running.clone()).unwrap_or_else(|e| {
+            eprintln!("Could not listen for FreeD on {address}: {e}");
+            std::process::exit(1)
+        });
+        println!("FreeD listener for camera IDs on {address} started.");
+        listener
+    }).collect();
+
     ndi::initialize().unwrap();
     println!("NDI library initialized.");
 
-    let ptz = Ptz::new(1).start_listening(running.clone());
-
-    println!("FreeD listener started on port 555{}.", ptz.num());
-
-    send_line(ptz, running);
+    let overlays: Vec<_> = ptzs.into_iter().map(|(ptz, lens, socket)| {
+        let running = running.clone();
+        thread::spawn(move || send_line(ptz, lens, socket, running))
+    }).collect();
+    for overlay in overlays {
+        overlay.join().unwrap();
+    }
 
     print!("Received interrupt, cleaning up... ");
-    // SAFETY: cleanup is done after send_line has returned; no more NDI in use
+    // SAFETY: cleanup is done after every send_line has returned; no more NDI in use
     unsafe { ndi::cleanup(); }
     println!("Done");
 }
 
+/// How often each overlay prints how it is doing
+const STATUS_INTERVAL: Duration = Duration::from_secs(5);
+
 const LINE: (Vector3<f32>, Vector3<f32>) = (Vector3::new(0.05, -0.36, -1.8), Vector3::new(0.05, 0.0, -1.8));
 const LINE2: (Vector3<f32>, Vector3<f32>) = (Vector3::new(-0.13, -0.18, -1.8), Vector3::new(0.23, -0.18, -1.8));
 const WB_0: (Vector3<f32>, Vector3<f32>) = (Vector3::new(-0.6, 0.11, -1.8), Vector3::new(0.6, 0.11, -1.8));
 const WB_1: (Vector3<f32>, Vector3<f32>) = (Vector3::new(-0.6, -0.79, -1.8), Vector3::new(0.6, -0.79, -1.8));
 
-fn send_line(ptz: Ptz, running: Arc<AtomicBool>) {
+/// `socket` is what the PTZ shares its socket with, if it is in a group,
+/// which is where its errors are counted
+fn send_line(ptz: Ptz, lens: LensProfile, socket: Option<Arc<Statistics>>, running: Arc<AtomicBool>) {
     let send = ndi::SendBuilder::new()
-        .ndi_name(format!("PTZ-0{} line overlay", ptz.num()))
+        .ndi_name(format!("{} line overlay", ptz.name()))
         .build()
         .unwrap();
 
     let mut frame = Frame::new(1920, 1080);
 
-    let mut camera = Camera::default();
+    let mut camera = Camera::new(lens);
 
     let mut avg_frame_interval = 1.0 / frame.video_data.frame_rate();
     let mut most_recent_print = SystemTime::now();
+    let mut tracking_before = TrackingState::Lost;
 
     while running.load(Ordering::Relaxed) {
         // Get the current time
@@ -62,32 +125,27 @@ fn send_line(ptz: Ptz, running: Arc<AtomicBool>) {
             thread::sleep(Duration::from_secs(1));
         }
 
-        let (yaw, pitch, zoom) = ptz.yaw_pitch_zoom();
-        camera.set_rotation(yaw, pitch, 0.0);
-        camera.set_zoom(zoom);
-        let p_0 = camera.project(LINE.0);
-        let p_1 = camera.project(LINE.1);
-        let p_2 = camera.project(LINE2.0);
-        let p_3 = camera.project(LINE2.1);
-        let p_4 = camera.project(WB_0.0);
-        let p_5 = camera.project(WB_0.1);
-        let p_6 = camera.project(WB_1.0);
-        let p_7 = camera.project(WB_1.1);
+        let pose = ptz.aligned();
+        camera.set_pose(&pose);
 
-        //println!("yaw: {yaw:.2}°, pitch: {pitch:.2}°, zoom: {zoom}");
+        // A frozen line on air is worse than no line
+        let tracking = ptz.tracking_state();
+        if tracking != tracking_before {
+            println!("Tracking of {} is {tracking}", ptz.name());
+            tracking_before = tracking;
+        }
 
-        frame.clear();
+        let opacity = match tracking {
+            TrackingState::Ok => 1.0,
+            TrackingState::Stale => 0.5,
+            TrackingState::Lost => 0.0,
+        };
 
-        frame.draw_thick_line(p_0, p_1, 8.0, (255, 127, 127));
-        frame.draw_thick_line(p_2, p_3, 8.0, (255, 127, 127));
-        frame.draw_thick_line(p_4, p_5, 4.0, (135, 84, 73));
-        frame.draw_thick_line(p_6, p_7, 4.0, (135, 84, 73));
-        frame.draw_thick_line(p_4, p_6, 4.0, (135, 84, 73));
-        frame.draw_thick_line(p_5, p_7, 4.0, (135, 84, 73));
-        frame.fill_circle(p_4.x as u32, p_4.y as u32, 170, 170, 135, 255);
-        frame.fill_circle(p_5.x as u32, p_5.y as u32, 170, 170, 135, 255);
-        frame.fill_circle(p_6.x as u32, p_6.y as u32, 170, 170, 135, 255);
-        frame.fill_circle(p_7.x as u32, p_7.y as u32, 170, 170, 135, 255);
+        frame.clear();
+        if opacity > 0.0 {
+            frame.set_opacity(opacity);
+            draw_overlay(&mut frame, &camera);
+        }
 
         // We now submit the frame. Note that this call will be clocked so that we end up submitting at exactly the specified frame rate.
         send.send_video(&frame.video_data);
@@ -96,12 +154,44 @@ fn send_line(ptz: Ptz, running: Arc<AtomicBool>) {
         let end_time = SystemTime::now();
         avg_frame_interval = 0.9 * avg_frame_interval + 0.1 * end_time.duration_since(start_time).unwrap().as_secs_f32();
 
-        if end_time.duration_since(most_recent_print).unwrap().as_secs_f32() > 0.5 && send.get_no_connections(0) > 0 {
-            // Move to previous line and clear it before printing
-            println!("\x1B[F\x1B[2KAverage fps is {:2.1}", 1.0 / avg_frame_interval);
+        if end_time.duration_since(most_recent_print).unwrap() > STATUS_INTERVAL && send.get_no_connections(0) > 0 {
+            // One short line, so the lines of several overlays don't run into
+            // each other
+            let stats = ptz.stats();
+            let errors = socket.as_ref().map_or(stats, |socket| socket.snapshot()).errors();
+            println!(
+                "{}: {:2.1} fps, FreeD {:.1} messages/s, {} errors",
+                ptz.name(), 1.0 / avg_frame_interval, stats.rate, errors,
+            );
             most_recent_print = end_time;
         }
     }
 
+    for (sender, count) in ptz.senders().rejected() {
+        println!("{} rejected {count} packets from {sender}", ptz.name());
+    }
+
     // Send instance is destroyed automatically when dropped
 }
+
+fn draw_overlay(frame: &mut Frame, camera: &Camera) {
+    let p_0 = camera.project(LINE.0);
+    let p_1 = camera.project(LINE.1);
+    let p_2 = camera.project(LINE2.0);
+    let p_3 = camera.project(LINE2.1);
+    let p_4 = camera.project(WB_0.0);
+    let p_5 = camera.project(WB_0.1);
+    let p_6 = camera.project(WB_1.0);
+    let p_7 = camera.project(WB_1.1);
+
+    frame.draw_thick_line(p_0, p_1, 8.0, (255, 127, 127));
+    frame.draw_thick_line(p_2, p_3, 8.0, (255, 127, 127));
+    frame.draw_thick_line(p_4, p_5, 4.0, (135, 84, 73));
+    frame.draw_thick_line(p_6, p_7, 4.0, (135, 84, 73));
+    frame.draw_thick_line(p_4, p_6, 4.0, (135, 84, 73));
+    frame.draw_thick_line(p_5, p_7, 4.0, (135, 84, 73));
+    frame.fill_circle(p_4.x as u32, p_4.y as u32, 170, 170, 135, 255);
+    frame.fill_circle(p_5.x as u32, p_5.y as u32, 170, 170, 135, 255);
+    frame.fill_circle(p_6.x as u32, p_6.y as u32, 170, 170, 135, 255);
+    frame.fill_circle(p_7.x as u32, p_7.y as u32, 170, 170, 135, 255);
+}
@@ -0,0 +1,175 @@
+//! Receiving FreeD on a tokio runtime, for async applications. Datagrams are
+//! handled like by the thread of [`Ptz::start_listening`], so the history,
+//! statistics and subscriptions of the PTZ work the same.
+
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::AtomicBool;
+use std::time::Instant;
+use tokio::net::UdpSocket;
+use tokio::runtime::Handle;
+use tokio::sync::mpsc::Receiver;
+use crate::freed::{Message, BROADCAST_CAMERA_ID};
+use crate::freed::recording::Recorder;
+use super::*;
+
+impl Ptz {
+    /// Like [`Ptz::start_listening`], but receive in a task on the current
+    /// tokio runtime instead of on a thread of its own. Fails if called
+    /// outside a runtime. [`Ptz::rebind`] keeps the listener on that runtime.
+    pub fn start_listening_async(mut self, running: Arc<AtomicBool>) -> std::io::Result<Self> {
+        let runtime = Handle::try_current().map_err(std::io::Error::other)?;
+        self.listen_async(self.address, running, runtime)?;
+        Ok(self)
+    }
+
+    /// Every new pose, like [`Ptz::subscribe`], to await in async code. The
+    /// stream ends when the PTZ is dropped.
+    pub fn pose_stream(&self) -> Receiver<Pose> {
+        self.subscribers.stream()
+    }
+
+    pub(super) fn listen_async(&mut self, address: SocketAddr, running: Arc<AtomicBool>, runtime: Handle) -> std::io::Result<()> {
+        // Bind first, so a failure leaves the current listener alone
+        let socket = std::net::UdpSocket::bind(address)?;
+        socket.set_nonblocking(true)?;
+        let socket = {
+            let _runtime = runtime.enter();
+            UdpSocket::from_std(socket)?
+        };
+        self.stop();
+        self.address = socket.local_addr()?;
+
+        let senders = self.senders.clone();
+        let statistics = self.statistics.clone();
+        let poll_address = self.poll_address;
+        let recorder = self.recorder.take();
+        let mut handler = Handler::new(self, true);
+        self.listener = Some(Listener::spawn_async(running, Some(self.address), runtime, recorder, move |running, recorder| async move {
+            let raw_forwarder = handler.raw_forwarder();
+            receive_loop(&socket, &running, &recorder, &statistics, poll_address, |recorder, datagram, source| {
+                receive_datagram(datagram, source, &senders, &statistics, raw_forwarder.as_deref(), &mut |freed, source| {
+                    handler.handle(freed, Some(source), recorder);
+                });
+            }).await;
+        }));
+
+        Ok(())
+    }
+}
+
+/// The async version of [`super::receive_loop`], which leaves the datagrams
+/// to `on_datagram`. They are handled with `recorder` locked, so they aren't
+/// once the listener was stopped.
+async fn receive_loop(
+    socket: &UdpSocket,
+    running: &Running,
+    recorder: &Mutex<Option<Recorder>>,
+    statistics: &Statistics,
+    poll_address: Option<SocketAddr>,
+    mut on_datagram: impl FnMut(&mut Option<Recorder>, &[u8], SocketAddr),
+) {
+    let mut buf = [0u8; 1500];
+    let poll = Message::poll(BROADCAST_CAMERA_ID).encode();
+    let mut last_poll = Instant::now() - Ptz::POLL_INTERVAL;
+    // Check `running` regularly, and don't wait forever for a reply to a poll
+    // that got lost
+    let timeout = match poll_address {
+        Some(..) => Ptz::POLL_INTERVAL,
+        None => Listener::CHECK_INTERVAL,
+    };
+
+    while running.get() {
+        if let Some(address) = poll_address {
+            if last_poll.elapsed() >= Ptz::POLL_INTERVAL {
+                if let Err(e) = socket.send_to(&poll, address).await {
+                    poll_failed(&e, address, statistics);
+                }
+                last_poll = Instant::now();
+            }
+        }
+
+        match tokio::time::timeout(timeout, socket.recv_from(&mut buf)).await {
+            Ok(Ok((amount, source))) => {
+                let mut recorder = recorder.lock().unwrap();
+                if !running.get() {
+                    break
+                }
+                on_datagram(&mut recorder, &buf[..amount], source);
+            }
+            Ok(Err(e)) => receive_failed(&e, statistics),
+            Err(..) => {}
+        }
+    }
+}
+
+#[test]
+fn test_async() {
+    let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+    runtime.block_on(async {
+        let running = Arc::new(AtomicBool::new(true));
+        let mut ptz = Ptz::from_config(&test_config()).start_listening_async(running.clone()).unwrap();
+        let mut poses = ptz.pose_stream();
+
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let mut freed = FreeD::zero();
+        freed.pan = 10.0;
+        socket.send_to(&freed.encode(), ptz.address()).await.unwrap();
+        socket.send_to(&[0xD1, 0x00], ptz.address()).await.unwrap();
+        let pose = tokio::time::timeout(RECEIVE_TIMEOUT, poses.recv()).await.unwrap().unwrap();
+        assert_eq!(pose.pan, 10.0);
+
+        // Still async after moving to another port
+        ptz.rebind(SocketAddr::from(([127, 0, 0, 1], 0))).unwrap();
+        freed.pan = 20.0;
+        socket.send_to(&freed.encode(), ptz.address()).await.unwrap();
+        let pose = tokio::time::timeout(RECEIVE_TIMEOUT, poses.recv()).await.unwrap().unwrap();
+        assert_eq!(pose.pan, 20.0);
+
+        let stats = ptz.stats();
+        assert_eq!((stats.packets, stats.messages, stats.wrong_length), (3, 2, 1));
+        ptz.stop();
+        assert!(!ptz.is_listening());
+    });
+
+    // Not without a runtime
+    assert!(Ptz::new(1).start_listening_async(Arc::new(AtomicBool::new(true))).is_err());
+}
+
+#[test]
+fn test_async_recording() {
+    use crate::freed::recording::Reader;
+
+    let path = std::env::temp_dir().join(format!("test_async_recording_{}.freed", std::process::id()));
+    let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+    runtime.block_on(async {
+        let running = Arc::new(AtomicBool::new(true));
+        let mut ptz = Ptz::from_config(&test_config())
+            .recording(Recorder::create(&path).unwrap())
+            .start_listening_async(running.clone())
+            .unwrap();
+        let mut poses = ptz.pose_stream();
+
+        // The recorder moves along to the new listener, and is handed back
+        // when it stops
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        for pan in [10.0, 20.0] {
+            let mut freed = FreeD::zero();
+            freed.pan = pan;
+            socket.send_to(&freed.encode(), ptz.address()).await.unwrap();
+            tokio::time::timeout(RECEIVE_TIMEOUT, poses.recv()).await.unwrap().unwrap();
+            ptz.rebind(SocketAddr::from(([127, 0, 0, 1], 0))).unwrap();
+        }
+        ptz.stop();
+        assert!(ptz.recorder.is_some());
+
+        // Nothing is handled after stopping, even though the task is still
+        // around for a bit
+        socket.send_to(&FreeD::zero().encode(), ptz.address()).await.unwrap();
+        assert!(tokio::time::timeout(Listener::CHECK_INTERVAL, poses.recv()).await.is_err());
+    });
+
+    let pans: Vec<_> = Reader::open(&path).unwrap().map(|record| record.unwrap().freed.pan).collect();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(pans, [10.0, 20.0]);
+}
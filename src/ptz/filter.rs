@@ -0,0 +1,255 @@
+use std::f32::consts::PI;
+use std::time::Instant;
+use serde::Deserialize;
+use crate::freed::{wrap_angle, FreeD};
+
+/// A noise filter for one axis. Some heads jitter by a few hundredths of a
+/// degree, which makes a thin line shimmer at long zoom.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case", deny_unknown_fields)]
+pub enum Filter {
+    #[default]
+    None,
+    /// Smooths more when the value changes slowly, and less when it changes
+    /// fast, so intentional moves don't lag. See
+    /// <https://gery.casiez.net/1euro/>.
+    OneEuro {
+        /// Cutoff frequency in Hz when the value stands still. Lower means
+        /// less jitter.
+        min_cutoff: f32,
+        /// How fast the cutoff frequency rises with speed. Higher means less
+        /// lag.
+        beta: f32,
+        /// Cutoff frequency in Hz for the speed itself
+        #[serde(default = "Filter::default_d_cutoff")]
+        d_cutoff: f32,
+    },
+    /// Kalman filter that assumes a constant velocity, so it doesn't lag
+    /// behind a steady move.
+    Kalman {
+        /// Variance of the acceleration, in units per second squared. Higher
+        /// means it follows changes in speed faster.
+        process_noise: f32,
+        /// Variance of the noise on the received values. Higher means
+        /// smoother.
+        measurement_noise: f32,
+    },
+}
+
+/// A filter for pan, tilt and zoom each
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Filters {
+    pub pan: Filter,
+    pub tilt: Filter,
+    pub zoom: Filter,
+}
+
+impl Filter {
+    fn default_d_cutoff() -> f32 {
+        1.0
+    }
+
+    /// What is wrong with the parameters, if anything. Out of range, they make
+    /// the filter freeze or diverge, or turn every value into NaN.
+    pub fn check(&self) -> Result<(), &'static str> {
+        let positive = |value: f32| value.is_finite() && value > 0.0;
+        match *self {
+            Filter::None => Ok(()),
+            Filter::OneEuro { min_cutoff, .. } if !positive(min_cutoff) => Err("min_cutoff must be positive"),
+            Filter::OneEuro { beta, .. } if !(beta.is_finite() && beta >= 0.0) => Err("beta must be finite and not negative"),
+            Filter::OneEuro { d_cutoff, .. } if !positive(d_cutoff) => Err("d_cutoff must be positive"),
+            Filter::OneEuro { .. } => Ok(()),
+            Filter::Kalman { process_noise, .. } if !positive(process_noise) => Err("process_noise must be positive"),
+            Filter::Kalman { measurement_noise, .. } if !positive(measurement_noise) => Err("measurement_noise must be positive"),
+            Filter::Kalman { .. } => Ok(()),
+        }
+    }
+}
+
+/// The state of a [`Filter`]
+#[derive(Debug, Clone)]
+enum State {
+    None,
+    OneEuro { value: f32, speed: f32 },
+    Kalman { value: f32, speed: f32, covariance: [[f32; 2]; 2] },
+}
+
+#[derive(Debug, Clone)]
+struct AxisFilter {
+    filter: Filter,
+    /// Whether the values are angles that wrap at ±180°
+    angle: bool,
+    /// Nothing until the first value
+    state: Option<State>,
+}
+
+impl AxisFilter {
+    fn new(filter: Filter, angle: bool) -> Self {
+        AxisFilter { filter, angle, state: None }
+    }
+
+    /// Filter `value`, received `dt` seconds after the previous one
+    fn apply(&mut self, value: f32, dt: f32) -> f32 {
+        let filtered = self.filter(value, dt);
+        if !self.angle {
+            return filtered
+        }
+
+        // Keep the state within ±180° too, however often the camera turns
+        if let Some(State::OneEuro { value, .. } | State::Kalman { value, .. }) = &mut self.state {
+            *value = wrap_angle(*value);
+        }
+        wrap_angle(filtered)
+    }
+
+    fn filter(&mut self, value: f32, dt: f32) -> f32 {
+        let Some(state) = &mut self.state else {
+            self.state = Some(match self.filter {
+                Filter::None => State::None,
+                Filter::OneEuro { .. } => State::OneEuro { value, speed: 0.0 },
+                Filter::Kalman { measurement_noise, .. } => State::Kalman {
+                    value,
+                    speed: 0.0,
+                    covariance: [[measurement_noise, 0.0], [0.0, measurement_noise]],
+                },
+            });
+            return value
+        };
+        // Filter angles without the jump at ±180°
+        let value = match state {
+            State::OneEuro { value: previous, .. } | State::Kalman { value: previous, .. } if self.angle => {
+                *previous + wrap_angle(value - *previous)
+            }
+            _ => value,
+        };
+        if dt <= 0.0 {
+            return match state {
+                State::None => value,
+                State::OneEuro { value, .. } | State::Kalman { value, .. } => *value,
+            }
+        }
+
+        match (self.filter, state) {
+            (Filter::OneEuro { min_cutoff, beta, d_cutoff }, State::OneEuro { value: filtered, speed }) => {
+                let smoothing = |cutoff: f32| 1.0 / (1.0 + 1.0 / (2.0 * PI * cutoff * dt));
+                *speed += smoothing(d_cutoff) * ((value - *filtered) / dt - *speed);
+                let cutoff = min_cutoff + beta * speed.abs();
+                *filtered += smoothing(cutoff) * (value - *filtered);
+                *filtered
+            }
+            (Filter::Kalman { process_noise, measurement_noise }, State::Kalman { value: filtered, speed, covariance: p }) => {
+                // Predict
+                *filtered += *speed * dt;
+                let q = process_noise;
+                *p = [
+                    [
+                        p[0][0] + dt * (p[1][0] + p[0][1]) + dt * dt * p[1][1] + q * dt.powi(4) / 4.0,
+                        p[0][1] + dt * p[1][1] + q * dt.powi(3) / 2.0,
+                    ],
+                    [
+                        p[1][0] + dt * p[1][1] + q * dt.powi(3) / 2.0,
+                        p[1][1] + q * dt * dt,
+                    ],
+                ];
+
+                // Update
+                let residual = value - *filtered;
+                let s = p[0][0] + measurement_noise;
+                let gain = [p[0][0] / s, p[1][0] / s];
+                *filtered += gain[0] * residual;
+                *speed += gain[1] * residual;
+                *p = [
+                    [(1.0 - gain[0]) * p[0][0], (1.0 - gain[0]) * p[0][1]],
+                    [p[1][0] - gain[1] * p[0][0], p[1][1] - gain[1] * p[0][1]],
+                ];
+                *filtered
+            }
+            _ => value,
+        }
+    }
+}
+
+/// Filters the pan, tilt and zoom of consecutive FreeD messages of a camera
+#[derive(Debug, Clone)]
+pub struct PoseFilter {
+    pan: AxisFilter,
+    tilt: AxisFilter,
+    zoom: AxisFilter,
+    /// When the previous message was received
+    previous: Option<Instant>,
+}
+
+impl PoseFilter {
+    pub fn new(filters: Filters) -> Self {
+        PoseFilter {
+            pan: AxisFilter::new(filters.pan, true),
+            tilt: AxisFilter::new(filters.tilt, false),
+            zoom: AxisFilter::new(filters.zoom, false),
+            previous: None,
+        }
+    }
+
+    /// Filter a message received at `time`
+    pub fn apply(&mut self, freed: FreeD, time: Instant) -> FreeD {
+        let dt = self.previous.map_or(0.0, |previous| time.saturating_duration_since(previous).as_secs_f32());
+        self.previous = Some(time);
+
+        FreeD {
+            pan: self.pan.apply(freed.pan, dt),
+            tilt: self.tilt.apply(freed.tilt, dt).clamp(-90.0, 90.0),
+            zoom: self.zoom.apply(freed.zoom as f32, dt).max(0.0).round() as u32,
+            ..freed
+        }
+    }
+}
+
+#[cfg(test)]
+fn filter_signal(filter: Filter, signal: impl Fn(f32) -> f32) -> Vec<f32> {
+    let mut axis = AxisFilter::new(filter, false);
+    // Alternating noise of ±0.05 on top of the signal, at 50 Hz
+    (0..200).map(|i| {
+        let noise = if i % 2 == 0 { 0.05 } else { -0.05 };
+        axis.apply(signal(i as f32 / 50.0) + noise, 0.02)
+    }).collect()
+}
+
+#[test]
+fn test_filters() {
+    let filters = [
+        Filter::OneEuro { min_cutoff: 0.5, beta: 0.5, d_cutoff: 1.0 },
+        Filter::Kalman { process_noise: 100.0, measurement_noise: 0.01 },
+    ];
+    for filter in filters {
+        // Standing still, most of the jitter is gone
+        let filtered = filter_signal(filter, |_| 10.0);
+        assert!(filtered[100..].iter().all(|v| (v - 10.0).abs() < 0.02), "{filter:?}: {filtered:?}");
+
+        // Panning at 20°/s, it doesn't lag more than a few tenths of a degree
+        let filtered = filter_signal(filter, |t| 20.0 * t);
+        let lag = filtered[100..].iter().enumerate().map(|(i, v)| (20.0 * (i + 100) as f32 / 50.0 - v).abs());
+        assert!(lag.fold(0.0, f32::max) < 0.3, "{filter:?}: {filtered:?}");
+    }
+
+    assert_eq!(filter_signal(Filter::None, |_| 10.0)[1], 9.95);
+}
+
+#[test]
+fn test_pan_wrap() {
+    let filters = Filters { pan: Filter::OneEuro { min_cutoff: 1.0, beta: 0.0, d_cutoff: 1.0 }, ..Filters::default() };
+    let mut filter = PoseFilter::new(filters);
+    let start = Instant::now();
+    let mut freed = FreeD::zero();
+
+    // Keeps turning the short way around, past ±180°
+    let mut previous = 0.0;
+    for i in 0..400 {
+        freed.pan = wrap_angle(170.0 + i as f32);
+        let filtered = filter.apply(freed.clone(), start + std::time::Duration::from_millis(20 * i));
+        if i > 0 {
+            let step = wrap_angle(filtered.pan - previous);
+            assert!(step > 0.0 && step < 1.01, "{i}: {previous} to {}", filtered.pan);
+        }
+        previous = filtered.pan;
+    }
+}
@@ -0,0 +1,57 @@
+use std::net::{SocketAddr, UdpSocket};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+/// Re-sends received FreeD, corrected or unchanged, to other hosts. A PTZ can only send
+/// FreeD to one IP address, while several systems may need it.
+pub struct Forwarder {
+    /// Separate from the listening socket, because on Windows an unreachable
+    /// destination makes the next receive on the sending socket fail
+    socket: UdpSocket,
+    destinations: Vec<Destination>,
+}
+
+struct Destination {
+    address: SocketAddr,
+    errors: AtomicU64,
+    /// Whether the last send failed, to report only changes
+    failing: AtomicBool,
+}
+
+impl Forwarder {
+    pub fn new(destinations: impl IntoIterator<Item = SocketAddr>) -> std::io::Result<Self> {
+        Ok(Forwarder {
+            socket: UdpSocket::bind("0.0.0.0:0")?,
+            destinations: destinations.into_iter()
+                .map(|address| Destination {
+                    address,
+                    errors: AtomicU64::new(0),
+                    failing: AtomicBool::new(false),
+                })
+                .collect(),
+        })
+    }
+
+    pub(crate) fn forward(&self, datagram: &[u8]) {
+        for destination in &self.destinations {
+            let address = destination.address;
+            match self.socket.send_to(datagram, address) {
+                Ok(..) => if destination.failing.swap(false, Ordering::Relaxed) {
+                    println!("Forwarding FreeD to {address} works again");
+                }
+                Err(e) => {
+                    destination.errors.fetch_add(1, Ordering::Relaxed);
+                    if !destination.failing.swap(true, Ordering::Relaxed) {
+                        println!("Error forwarding FreeD to {address}: {e}");
+                    }
+                }
+            }
+        }
+    }
+
+    /// The number of failed sends for every destination
+    pub fn errors(&self) -> Vec<(SocketAddr, u64)> {
+        self.destinations.iter()
+            .map(|d| (d.address, d.errors.load(Ordering::Relaxed)))
+            .collect()
+    }
+}
@@ -0,0 +1,95 @@
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Instant, SystemTime};
+use crate::freed::{FreeD, Transform};
+use crate::freed::recording::Recorder;
+use super::{Forwarder, History, Pose, PoseFilter, Ptz, Sample, Subscribers};
+
+/// What happens to every received message of a PTZ: it is recorded,
+/// corrected, forwarded, filtered and stored, in that order. Every listener
+/// uses one, so they all handle messages alike.
+pub(crate) struct Handler {
+    history: Arc<Mutex<History>>,
+    subscribers: Arc<Subscribers>,
+    filter: PoseFilter,
+    transform: Option<Transform>,
+    forwarder: Option<Arc<Forwarder>>,
+    /// Messages with another camera ID are ignored
+    camera_id: Option<u8>,
+    /// Whether whole datagrams are forwarded as they are received, see
+    /// [`Handler::raw_forwarder`]
+    raw: bool,
+}
+
+impl Handler {
+    /// Handle messages for `ptz`. Over UDP (`datagrams`), the forwarder gets
+    /// whole datagrams if it can, otherwise every message is forwarded after
+    /// it was corrected.
+    pub(crate) fn new(ptz: &Ptz, datagrams: bool) -> Self {
+        Handler {
+            history: ptz.history.clone(),
+            subscribers: ptz.subscribers.clone(),
+            filter: PoseFilter::new(ptz.filters),
+            transform: ptz.transform.clone(),
+            forwarder: ptz.forwarder.clone(),
+            camera_id: ptz.camera_id,
+            raw: datagrams && ptz.transform.is_none() && ptz.camera_id.is_none(),
+        }
+    }
+
+    /// Like [`Handler::new`], but nothing is forwarded, e.g. for a replay
+    pub(crate) fn without_forwarding(ptz: &Ptz) -> Self {
+        Handler { forwarder: None, ..Handler::new(ptz, false) }
+    }
+
+    /// For a [`super::PtzGroup`], which routes by camera ID itself, so
+    /// messages are handled whatever the camera ID of `ptz`. Nothing is
+    /// forwarded.
+    pub(crate) fn routed(ptz: &Ptz) -> Self {
+        Handler { camera_id: None, ..Handler::without_forwarding(ptz) }
+    }
+
+    /// Who to give the datagrams to before even decoding them. Only without
+    /// corrections or a camera ID, because those can only be applied to
+    /// decoded messages.
+    pub(crate) fn raw_forwarder(&self) -> Option<Arc<Forwarder>> {
+        self.forwarder.clone().filter(|_| self.raw)
+    }
+
+    /// Handle a message received from `source`, if over the network
+    pub(crate) fn handle(&mut self, freed: FreeD, source: Option<SocketAddr>, recorder: &mut Option<Recorder>) {
+        if self.camera_id.is_some_and(|camera_id| camera_id != freed.camera_id) {
+            return
+        }
+        record(recorder, source, &freed);
+        let freed = match &self.transform {
+            Some(transform) => transform.apply(&freed),
+            None => freed,
+        };
+        if let Some(forwarder) = self.forwarder.as_ref().filter(|_| !self.raw) {
+            forwarder.forward(&freed.encode());
+        }
+        self.store(freed);
+    }
+
+    /// Filter a message, add it to the history and send it to the subscribers
+    fn store(&mut self, freed: FreeD) {
+        let received = Instant::now();
+        let sample = Sample { freed: self.filter.apply(freed, received), received };
+        // Subscribers find it in the history too
+        let pose = Pose::from(&sample);
+        self.history.lock().unwrap().push(sample);
+        self.subscribers.publish(&pose);
+    }
+}
+
+/// Write a received message to the recorder, if any. Recording stops after an
+/// error, so that a full disk does not flood the console.
+fn record(recorder: &mut Option<Recorder>, source: Option<SocketAddr>, freed: &FreeD) {
+    if let Some(r) = recorder {
+        if let Err(e) = r.write(SystemTime::now(), source, freed) {
+            println!("Error recording FreeD, recording stopped: {e}");
+            *recorder = None;
+        }
+    }
+}
@@ -0,0 +1,204 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+use crate::freed::{wrap_angle, FreeD};
+use super::Sample;
+
+/// The most recent samples of a camera, oldest first, so the FreeD can be
+/// looked up at any moment instead of only at the last received message.
+#[derive(Debug, Clone)]
+pub struct History {
+    samples: VecDeque<Sample>,
+    capacity: usize,
+}
+
+impl History {
+    /// A few seconds of samples at 50 Hz
+    pub const DEFAULT_CAPACITY: usize = 256;
+
+    pub fn new(capacity: usize) -> Self {
+        History {
+            samples: VecDeque::with_capacity(capacity),
+            capacity: capacity.max(1),
+        }
+    }
+
+    /// Add a sample, dropping the oldest one if the history is full. Samples
+    /// that arrive out of order are put in their place.
+    pub fn push(&mut self, sample: Sample) {
+        if self.samples.len() == self.capacity {
+            self.samples.pop_front();
+        }
+        let index = self.samples.partition_point(|s| s.received <= sample.received);
+        self.samples.insert(index, sample);
+    }
+
+    pub fn latest(&self) -> Option<&Sample> {
+        self.samples.back()
+    }
+
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = &Sample> {
+        self.samples.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.samples.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+
+    /// The FreeD at `time`, interpolated between the samples around it.
+    /// Before the oldest or after the latest sample, that sample is returned
+    /// as is.
+    pub fn at(&self, time: Instant) -> Option<FreeD> {
+        let next = self.samples.partition_point(|s| s.received <= time);
+        if next == 0 {
+            return self.samples.front().map(|s| s.freed.clone())
+        } else if next == self.samples.len() {
+            return self.samples.back().map(|s| s.freed.clone())
+        }
+
+        let (a, b) = (&self.samples[next - 1], &self.samples[next]);
+        let f = (time - a.received).as_secs_f32() / (b.received - a.received).as_secs_f32();
+        Some(a.freed.interpolate(&b.freed, f))
+    }
+
+    /// The FreeD at `time`, like [`History::at`], but after the latest sample
+    /// it is extrapolated from the velocity and acceleration over the last
+    /// three samples, for at most `limit`. Only pan, tilt and zoom are
+    /// extrapolated.
+    pub fn predict(&self, time: Instant, limit: Duration) -> Option<FreeD> {
+        let latest = self.latest()?;
+        if time <= latest.received {
+            return self.at(time)
+        }
+        let t = (time - latest.received).min(limit).as_secs_f32();
+
+        let mut samples = self.samples.iter().rev().skip(1);
+        let Some(previous) = samples.next() else {
+            return Some(latest.freed.clone())
+        };
+        let before = samples.next();
+
+        let (a, b) = (&previous.freed, &latest.freed);
+        let dt = (latest.received - previous.received).as_secs_f32();
+        if dt <= 0.0 {
+            return Some(b.clone())
+        }
+        let axis = |value: fn(&FreeD) -> f32, difference: fn(f32, f32) -> f32| {
+            let velocity = difference(value(a), value(b)) / dt;
+            let acceleration = match before {
+                Some(before) if previous.received > before.received => {
+                    let dt_before = (previous.received - before.received).as_secs_f32();
+                    let velocity_before = difference(value(&before.freed), value(a)) / dt_before;
+                    (velocity - velocity_before) / ((dt + dt_before) / 2.0)
+                }
+                _ => 0.0,
+            };
+            displacement(velocity, acceleration, t)
+        };
+
+        let pan = axis(|f| f.pan, |a, b| wrap_angle(b - a));
+        let tilt = axis(|f| f.tilt, |a, b| b - a);
+        let zoom = axis(|f| f.zoom as f32, |a, b| b - a);
+
+        Some(FreeD {
+            pan: wrap_angle(b.pan + pan),
+            tilt: (b.tilt + tilt).clamp(-90.0, 90.0),
+            zoom: (b.zoom as f32 + zoom).max(0.0).round() as u32,
+            ..b.clone()
+        })
+    }
+}
+
+/// How far something moves in `t` seconds, starting at `velocity` with a
+/// constant `acceleration`. Slowing down stops at zero velocity instead of
+/// going back, and speeding up at most doubles the distance, so the
+/// prediction doesn't overshoot when the camera suddenly stops.
+fn displacement(velocity: f32, acceleration: f32, t: f32) -> f32 {
+    if velocity == 0.0 {
+        return 0.0
+    }
+    if acceleration * velocity < 0.0 {
+        let t = t.min(-velocity / acceleration);
+        return velocity * t + 0.5 * acceleration * t * t
+    }
+    let linear = velocity * t;
+    linear + (0.5 * acceleration * t * t).clamp(-linear.abs(), linear.abs())
+}
+
+impl Default for History {
+    fn default() -> Self {
+        History::new(Self::DEFAULT_CAPACITY)
+    }
+}
+
+#[cfg(test)]
+fn sample(start: Instant, millis: u64, pan: f32) -> Sample {
+    let mut freed = FreeD::zero();
+    freed.pan = pan;
+    Sample { freed, received: start + Duration::from_millis(millis) }
+}
+
+#[test]
+fn test_at() {
+    let start = Instant::now();
+    let mut history = History::new(3);
+    assert_eq!(history.at(start), None);
+
+    for (millis, pan) in [(0, 0.0), (20, 10.0), (40, 170.0), (60, -170.0)] {
+        history.push(sample(start, millis, pan));
+    }
+    // The first one was dropped
+    assert_eq!(history.len(), 3);
+    assert_eq!(history.at(start).unwrap().pan, 10.0);
+    assert_eq!(history.at(start + Duration::from_millis(30)).unwrap().pan, 90.0);
+    assert_eq!(history.at(start + Duration::from_millis(55)).unwrap().pan, -175.0);
+    assert_eq!(history.at(start + Duration::from_secs(1)).unwrap().pan, -170.0);
+}
+
+#[test]
+fn test_out_of_order() {
+    let start = Instant::now();
+    let mut history = History::default();
+    history.push(sample(start, 20, 2.0));
+    history.push(sample(start, 0, 0.0));
+    history.push(sample(start, 10, 1.0));
+
+    assert_eq!(history.iter().map(|s| s.freed.pan).collect::<Vec<_>>(), [0.0, 1.0, 2.0]);
+    assert_eq!(history.latest().unwrap().freed.pan, 2.0);
+}
+
+#[test]
+fn test_predict() {
+    let start = Instant::now();
+    let millis = |millis| start + Duration::from_millis(millis);
+    let limit = Duration::from_millis(100);
+    let mut history = History::default();
+    assert_eq!(history.predict(start, limit), None);
+
+    // Constant velocity, 1° per 20 ms, over the 180° boundary
+    for (time, pan) in [(0, 178.0), (20, 179.0), (40, 180.0)] {
+        history.push(sample(start, time, pan));
+    }
+    assert_eq!(history.predict(millis(30), limit).unwrap().pan, 179.5);
+    assert_eq!(history.predict(millis(80), limit).unwrap().pan, -178.0);
+    // Not further than the limit
+    assert_eq!(history.predict(millis(1000), limit).unwrap().pan, -175.0);
+
+    // A sudden stop: no prediction at all
+    history.push(sample(start, 60, 180.0));
+    assert_eq!(history.predict(millis(100), limit).unwrap().pan, 180.0);
+}
+
+#[test]
+fn test_displacement() {
+    assert_eq!(displacement(10.0, 0.0, 0.5), 5.0);
+    // Slowing down stops after 1 s, instead of going back
+    assert_eq!(displacement(10.0, -10.0, 3.0), 5.0);
+    assert_eq!(displacement(-10.0, 10.0, 3.0), -5.0);
+    // Speeding up at most doubles the distance
+    assert_eq!(displacement(1.0, 100.0, 1.0), 2.0);
+    assert_eq!(displacement(0.0, 100.0, 1.0), 0.0);
+}
@@ -0,0 +1,142 @@
+use std::net::SocketAddr;
+use std::sync::Arc;
+#[cfg(feature = "tokio")]
+use std::sync::{Mutex, PoisonError};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::JoinHandle;
+use std::time::Duration;
+use crate::freed::recording::Recorder;
+
+/// Whether a listener should keep going: until the application stops, or
+/// until the listener itself is stopped
+#[derive(Debug, Clone)]
+pub(crate) struct Running {
+    application: Arc<AtomicBool>,
+    listener: Arc<AtomicBool>,
+}
+
+impl Running {
+    pub(crate) fn get(&self) -> bool {
+        self.application.load(Ordering::Relaxed) && self.listener.load(Ordering::Relaxed)
+    }
+}
+
+/// A thread receiving FreeD, or a task with the `tokio` feature. It stops when
+/// the `running` flag it was started with is cleared, or when it is stopped
+/// or dropped itself. Nothing is received after it was stopped.
+#[derive(Debug)]
+pub struct Listener {
+    running: Running,
+    /// Where it listens, if over UDP
+    address: Option<SocketAddr>,
+    task: Option<Task>,
+}
+
+#[derive(Debug)]
+enum Task {
+    /// Hands back the recorder, so it can be used by the next listener
+    Thread(JoinHandle<Option<Recorder>>),
+    /// With the runtime it runs on, to start the next listener there too, and
+    /// the recorder it writes to. The task only handles a datagram with the
+    /// recorder locked and while it is running, so once the recorder is
+    /// taken back nothing is handled anymore.
+    #[cfg(feature = "tokio")]
+    Async(tokio::task::JoinHandle<()>, tokio::runtime::Handle, Arc<Mutex<Option<Recorder>>>),
+}
+
+impl Listener {
+    /// How often a listener checks whether it should stop, if nothing is
+    /// received in the meantime
+    pub(crate) const CHECK_INTERVAL: Duration = Duration::from_millis(100);
+
+    pub(crate) fn spawn(
+        running: Arc<AtomicBool>,
+        address: Option<SocketAddr>,
+        listen: impl FnOnce(Running) -> Option<Recorder> + Send + 'static,
+    ) -> Self {
+        let running = Running { application: running, listener: Arc::new(AtomicBool::new(true)) };
+        let thread = {
+            let running = running.clone();
+            std::thread::spawn(move || listen(running))
+        };
+
+        Listener { running, address, task: Some(Task::Thread(thread)) }
+    }
+
+    /// Spawn a task on `runtime`, instead of a thread. The task gets the
+    /// recorder behind a lock, see [`Task::Async`].
+    #[cfg(feature = "tokio")]
+    pub(crate) fn spawn_async<F>(
+        running: Arc<AtomicBool>,
+        address: Option<SocketAddr>,
+        runtime: tokio::runtime::Handle,
+        recorder: Option<Recorder>,
+        listen: impl FnOnce(Running, Arc<Mutex<Option<Recorder>>>) -> F,
+    ) -> Self
+    where
+        F: std::future::Future<Output = ()> + Send + 'static,
+    {
+        let running = Running { application: running, listener: Arc::new(AtomicBool::new(true)) };
+        let recorder = Arc::new(Mutex::new(recorder));
+        let task = runtime.spawn(listen(running.clone(), recorder.clone()));
+
+        Listener { running, address, task: Some(Task::Async(task, runtime, recorder)) }
+    }
+
+    /// The runtime of a task
+    #[cfg(feature = "tokio")]
+    pub(crate) fn runtime(&self) -> Option<tokio::runtime::Handle> {
+        match &self.task {
+            Some(Task::Async(_, runtime, _)) => Some(runtime.clone()),
+            _ => None,
+        }
+    }
+
+    /// The address of the UDP socket, or nothing for other transports
+    pub fn address(&self) -> Option<SocketAddr> {
+        self.address
+    }
+
+    /// The flag this listener was started with
+    pub(crate) fn application(&self) -> Arc<AtomicBool> {
+        self.running.application.clone()
+    }
+
+    /// Whether the thread is still receiving. Not after it was stopped, and
+    /// not if it stopped because of an error.
+    pub fn is_running(&self) -> bool {
+        match &self.task {
+            Some(Task::Thread(thread)) => !thread.is_finished(),
+            #[cfg(feature = "tokio")]
+            Some(Task::Async(task, ..)) => !task.is_finished(),
+            None => false,
+        }
+    }
+
+    /// Stop the thread and wait for it to finish, which takes at most
+    /// [`Listener::CHECK_INTERVAL`]. A task can't be waited for without
+    /// blocking its runtime, so it ends by itself within that time, and only
+    /// then closes its socket.
+    pub fn stop(mut self) {
+        self.finish();
+    }
+
+    /// Stop the thread or task, and take back the recorder it was writing to
+    pub(crate) fn finish(&mut self) -> Option<Recorder> {
+        self.running.listener.store(false, Ordering::Relaxed);
+        match self.task.take()? {
+            Task::Thread(thread) => thread.join().unwrap_or_else(|_| {
+                println!("FreeD listener stopped with a panic");
+                None
+            }),
+            #[cfg(feature = "tokio")]
+            Task::Async(_, _, recorder) => recorder.lock().unwrap_or_else(PoisonError::into_inner).take(),
+        }
+    }
+}
+
+impl Drop for Listener {
+    fn drop(&mut self) {
+        self.finish();
+    }
+}
@@ -0,0 +1,1183 @@
+use std::collections::{HashMap, HashSet};
+use std::io::{ErrorKind, Read};
+use std::net::{IpAddr, SocketAddr, UdpSocket};
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::AtomicBool;
+use std::sync::mpsc::{Receiver, RecvTimeoutError};
+use std::time::{Duration, Instant};
+use serialport::{DataBits, Parity, StopBits};
+use crate::config::PtzConfig;
+use crate::freed::{Decoder, FreeD, Message, Transform, BROADCAST_CAMERA_ID};
+use crate::freed::recording::{Reader, Record, Recorder};
+use handler::Handler;
+use listener::Running;
+use subscribers::Subscribers;
+
+pub use filter::{Filter, Filters, PoseFilter};
+pub use forward::Forwarder;
+pub use history::History;
+pub use listener::Listener;
+pub use pose::Pose;
+pub use senders::SenderFilter;
+pub use stats::{Statistics, Stats};
+pub use subscribers::Subscription;
+pub use tracking::{Sample, Timeouts, TrackingState};
+
+#[cfg(feature = "tokio")]
+mod async_listener;
+mod filter;
+mod forward;
+mod handler;
+mod history;
+mod listener;
+mod pose;
+mod senders;
+mod stats;
+mod subscribers;
+mod tracking;
+
+pub struct Ptz {
+    name: String,
+    /// Where to listen for FreeD
+    address: SocketAddr,
+    senders: Arc<SenderFilter>,
+    history: Arc<Mutex<History>>,
+    subscribers: Arc<Subscribers>,
+    statistics: Arc<Statistics>,
+    timeouts: Timeouts,
+    /// How far ahead to predict the FreeD, to make up for the latency of
+    /// rendering
+    prediction: Duration,
+    /// How far behind to render, for video that arrives later than FreeD
+    delay: Duration,
+    /// Address to send D0 polls to, for cameras in polled mode
+    poll_address: Option<SocketAddr>,
+    /// Only use FreeD with this camera ID
+    camera_id: Option<u8>,
+    recorder: Option<Recorder>,
+    forwarder: Option<Arc<Forwarder>>,
+    transform: Option<Transform>,
+    filters: Filters,
+    /// Receiving FreeD, if started
+    listener: Option<Listener>,
+}
+
+/// How fast to replay a recording
+pub enum Pace {
+    /// Keep the timing of the recording, sped up by this (positive) factor:
+    /// 1.0 is real time.
+    Speed(f32),
+    /// Advance one message every time a value is received. Stops when the
+    /// sender is dropped.
+    Step(Receiver<()>),
+}
+
+impl Ptz {
+    /// The FreeD manual allows at most 100 polls per second
+    const POLL_INTERVAL: Duration = Duration::from_millis(10);
+    /// Baud rate of FreeD over RS-232/RS-422, according to the manual
+    pub const SERIAL_BAUD_RATE: u32 = 38400;
+    /// How many poses a subscription holds, see [`Ptz::subscribe`]
+    pub const SUBSCRIPTION_CAPACITY: usize = Subscribers::CHANNEL_CAPACITY;
+
+    /// A PTZ of LVC, which sends to port 555{num}
+    pub fn new(ptz_num: u8) -> Self {
+        Self::from_config(&PtzConfig::lvc(ptz_num))
+    }
+
+    pub fn from_config(config: &PtzConfig) -> Self {
+        Ptz {
+            name: config.name.clone(),
+            address: config.address(),
+            senders: Arc::new(SenderFilter::new(config.senders.iter().copied())),
+            history: Arc::new(Mutex::new(History::new(config.history_capacity()))),
+            subscribers: Arc::new(Subscribers::default()),
+            statistics: Arc::new(Statistics::default()),
+            timeouts: config.timeouts(),
+            prediction: config.prediction(),
+            delay: config.delay(),
+            filters: config.filter,
+            poll_address: config.poll_address(),
+            camera_id: config.camera_id,
+            recorder: None,
+            forwarder: None,
+            transform: config.transform.clone(),
+            listener: None,
+        }
+    }
+
+    /// Correct every received message with `transform` before it is used. If
+    /// the PTZ is also forwarding, the corrected messages are forwarded instead
+    /// of the received ones.
+    pub fn transforming(mut self, transform: Transform) -> Self {
+        self.transform = Some(transform);
+        self
+    }
+
+    /// Predict the pose this far ahead in [`Ptz::predicted`] and
+    /// [`Ptz::aligned`], from how fast the camera moves.
+    pub fn predicting(mut self, horizon: Duration) -> Self {
+        self.prediction = horizon;
+        self
+    }
+
+    /// Delay the pose in [`Ptz::aligned`] by this much, to line it up with
+    /// video that arrives later. The history must be long enough to cover it.
+    pub fn delayed(mut self, delay: Duration) -> Self {
+        self.delay = delay;
+        self
+    }
+
+    /// Filter the noise out of every received message, before it is used
+    pub fn filtering(mut self, filters: Filters) -> Self {
+        self.filters = filters;
+        self
+    }
+
+    /// Keep this many samples, instead of [`History::DEFAULT_CAPACITY`]
+    pub fn history(mut self, capacity: usize) -> Self {
+        self.history = Arc::new(Mutex::new(History::new(capacity)));
+        self
+    }
+
+    /// Change after how long without messages tracking is considered stale
+    /// or lost
+    pub fn timeouts(mut self, timeouts: Timeouts) -> Self {
+        self.timeouts = timeouts;
+        self
+    }
+
+    /// Re-send every message this PTZ receives to the destinations of
+    /// `forwarder`. Over UDP, datagrams are forwarded as they are received,
+    /// before decoding.
+    pub fn forwarding(mut self, forwarder: Forwarder) -> Self {
+        self.forwarder = Some(Arc::new(forwarder));
+        self
+    }
+
+    /// Only accept FreeD from these addresses, instead of from anywhere.
+    pub fn allowing(mut self, senders: impl IntoIterator<Item = IpAddr>) -> Self {
+        self.senders = Arc::new(SenderFilter::new(senders));
+        self
+    }
+
+    /// Only use FreeD with this camera ID, instead of any. Messages of other
+    /// cameras are still counted in [`Ptz::stats`]. Several PTZs on one port
+    /// are better received by a [`PtzGroup`].
+    pub fn camera(mut self, camera_id: u8) -> Self {
+        self.camera_id = Some(camera_id);
+        self
+    }
+
+    /// Which senders are allowed, and which others were rejected
+    pub fn senders(&self) -> &SenderFilter {
+        &self.senders
+    }
+
+    /// Get every new pose, filtered but not predicted or delayed, as soon as
+    /// it is received. If the receiver falls more than
+    /// [`Ptz::SUBSCRIPTION_CAPACITY`] poses behind, it misses the newest ones
+    /// until it catches up. Dropping the receiver unsubscribes.
+    pub fn subscribe(&self) -> Receiver<Pose> {
+        self.subscribers.channel()
+    }
+
+    /// Call `callback` with every new pose, like [`Ptz::subscribe`], until
+    /// the returned subscription is dropped. It is called on the listening
+    /// thread, so it should be quick. If it panics, it is unsubscribed.
+    pub fn on_pose(&self, callback: impl FnMut(&Pose) + Send + 'static) -> Subscription {
+        self.subscribers.callback(callback)
+    }
+
+    /// What the listener received so far, to tell whether the tracking feed
+    /// is healthy. For a PTZ in a [`PtzGroup`] only its position messages, see
+    /// [`PtzGroup::statistics`].
+    pub fn stats(&self) -> Stats {
+        self.statistics.snapshot()
+    }
+
+    pub fn forwarder(&self) -> Option<&Forwarder> {
+        self.forwarder.as_deref()
+    }
+
+    /// Write every FreeD message this PTZ receives to `recorder`.
+    pub fn recording(mut self, recorder: Recorder) -> Self {
+        self.recorder = Some(recorder);
+        self
+    }
+
+    /// Poll the camera at `address` for position updates, instead of waiting
+    /// for it to stream them. For cameras configured in polled mode.
+    pub fn polled(mut self, address: SocketAddr) -> Self {
+        self.poll_address = Some(address);
+        self
+    }
+
+    /// Start receiving FreeD the way `config` says, usually the one this PTZ
+    /// was made from: replayed from a file, from a serial port or over UDP,
+    /// and recorded if it should be. For PTZs in a [`PtzGroup`], the group listens instead.
+    pub fn start(mut self, config: &PtzConfig, running: Arc<AtomicBool>) -> std::io::Result<Self> {
+        // Say which file or address went wrong
+        let about = |what: &dyn std::fmt::Display, e: std::io::Error| std::io::Error::new(e.kind(), format!("{what}: {e}"));
+        if let Some(path) = &config.record {
+            self = self.recording(Recorder::create(path).map_err(|e| about(&path.display(), e))?);
+        }
+        match (&config.replay, &config.serial) {
+            (Some(path), _) => self.start_replay(Reader::open(path).map_err(|e| about(&path.display(), e))?, Pace::Speed(1.0), running),
+            (None, Some(path)) => self.start_listening_serial(path, config.baud_rate(), running).map_err(|e| about(path, e.into())),
+            (None, None) => {
+                let address = self.address;
+                self.start_listening(running).map_err(|e| about(&address, e))
+            }
+        }
+    }
+
+    /// Receive FreeD over UDP on the address of this PTZ, until `running` is
+    /// cleared or the PTZ is stopped or dropped. Fails if the address can't be
+    /// bound, e.g. because another program uses the port.
+    pub fn start_listening(mut self, running: Arc<AtomicBool>) -> std::io::Result<Self> {
+        self.listen(self.address, running)?;
+        Ok(self)
+    }
+
+    /// Listen on `address` from now on. If this PTZ is listening already, the
+    /// listener moves there; if `address` can't be bound, it keeps listening
+    /// where it was. Otherwise the address is used by the next
+    /// [`Ptz::start_listening`].
+    ///
+    /// An async listener closes its socket only some time after it was
+    /// stopped, so it can't move to the same port on another interface.
+    pub fn rebind(&mut self, address: SocketAddr) -> std::io::Result<()> {
+        match &self.listener {
+            Some(listener) => {
+                let running = listener.application();
+                // An async listener stays async
+                #[cfg(feature = "tokio")]
+                if let Some(runtime) = listener.runtime() {
+                    return self.listen_async(address, running, runtime)
+                }
+                self.listen(address, running)
+            }
+            None => {
+                self.address = address;
+                Ok(())
+            }
+        }
+    }
+
+    /// Stop receiving FreeD. Nothing is received after this returns, and
+    /// what was received so far is kept, like the recorder.
+    pub fn stop(&mut self) {
+        if let Some(mut listener) = self.listener.take() {
+            if let Some(recorder) = listener.finish() {
+                self.recorder = Some(recorder);
+            }
+        }
+    }
+
+    /// Whether FreeD is being received. Not after [`Ptz::stop`], nor after
+    /// `running` was cleared.
+    pub fn is_listening(&self) -> bool {
+        self.listener.as_ref().is_some_and(Listener::is_running)
+    }
+
+    fn listen(&mut self, address: SocketAddr, running: Arc<AtomicBool>) -> std::io::Result<()> {
+        // Bind first, so a failure leaves the current listener alone. The port
+        // it listens on can't be bound again though, even on another
+        // interface, so then it is stopped first and started again if binding
+        // fails.
+        let previous = self.listener.as_ref().and_then(Listener::address);
+        let same_port = previous.filter(|previous| previous.port() == address.port());
+        if same_port.is_some() {
+            self.stop();
+        }
+        let socket = match self.bind(address) {
+            Ok(socket) => socket,
+            Err(e) => {
+                if let Some(previous) = same_port {
+                    if let Err(e) = self.listen(previous, running) {
+                        println!("Could not listen on {previous} again: {e}");
+                    }
+                }
+                return Err(e)
+            }
+        };
+        self.stop();
+        self.address = socket.local_addr()?;
+
+        let senders = self.senders.clone();
+        let statistics = self.statistics.clone();
+        let poll_address = self.poll_address;
+        let mut recorder = self.recorder.take();
+        let mut handler = Handler::new(self, true);
+        self.listener = Some(Listener::spawn(running, Some(self.address), move |running| {
+            let raw_forwarder = handler.raw_forwarder();
+            receive_loop(&socket, &running, &senders, &statistics, poll_address, raw_forwarder.as_deref(), |freed, source| {
+                handler.handle(freed, Some(source), &mut recorder);
+            });
+            recorder
+        }));
+
+        Ok(())
+    }
+
+    fn bind(&self, address: SocketAddr) -> std::io::Result<UdpSocket> {
+        let socket = UdpSocket::bind(address)?;
+        // Check `running` regularly, and don't wait forever for a reply to a
+        // poll that got lost
+        socket.set_read_timeout(Some(match self.poll_address {
+            Some(..) => Self::POLL_INTERVAL,
+            None => Listener::CHECK_INTERVAL,
+        }))?;
+        Ok(socket)
+    }
+
+    /// Receive FreeD from a serial port, for heads that don't support UDP.
+    /// The port is set to 8 data bits, odd parity and 1 stop bit, as the FreeD
+    /// manual prescribes.
+    pub fn start_listening_serial(mut self, path: &str, baud_rate: u32, running: Arc<AtomicBool>) -> serialport::Result<Self> {
+        let mut port = serialport::new(path, baud_rate)
+            .data_bits(DataBits::Eight)
+            .parity(Parity::Odd)
+            .stop_bits(StopBits::One)
+            // Check `running` regularly, even if the head is silent
+            .timeout(Listener::CHECK_INTERVAL)
+            .open()?;
+        self.stop();
+
+        let statistics = self.statistics.clone();
+        let mut recorder = self.recorder.take();
+        let mut handler = Handler::new(&self, false);
+        let path = path.to_string();
+        self.listener = Some(Listener::spawn(running, None, move |running| {
+            serial_loop(&mut port, &running, &path, &statistics, |freed| handler.handle(freed, None, &mut recorder));
+            recorder
+        }));
+
+        Ok(self)
+    }
+
+    /// Feed recorded FreeD messages to this PTZ, instead of live ones. They are
+    /// corrected like live messages, but not forwarded. Fails if the speed is
+    /// not a positive number.
+    pub fn start_replay(
+        mut self,
+        records: impl Iterator<Item = std::io::Result<Record>> + Send + 'static,
+        pace: Pace,
+        running: Arc<AtomicBool>,
+    ) -> std::io::Result<Self> {
+        if let Pace::Speed(speed) = pace {
+            if !(speed.is_finite() && speed > 0.0) {
+                return Err(std::io::Error::new(ErrorKind::InvalidInput, format!("invalid replay speed {speed}")))
+            }
+        }
+        self.stop();
+
+        let mut handler = Handler::without_forwarding(&self);
+        self.listener = Some(Listener::spawn(running, None, move |running| {
+            // When the first record was replayed, and when it was recorded
+            let mut start = None;
+
+            'records: for record in records {
+                if !running.get() {
+                    break;
+                }
+                let record = match record {
+                    Ok(record) => record,
+                    Err(e) => {
+                        println!("Error reading recording: {e}");
+                        break;
+                    }
+                };
+
+                // Wait in short steps, to notice when to stop
+                match &pace {
+                    Pace::Speed(speed) => {
+                        let (replay_start, record_start) = *start.get_or_insert((Instant::now(), record.time));
+                        let offset = record.time.duration_since(record_start).unwrap_or_default().as_secs_f64() / *speed as f64;
+                        // Never, if it is too far off to even tell when
+                        let due = Duration::try_from_secs_f64(offset).ok().and_then(|offset| replay_start.checked_add(offset));
+                        while let Some(wait) = due.map_or(Some(Listener::CHECK_INTERVAL), |due| due.checked_duration_since(Instant::now())) {
+                            if !running.get() {
+                                break 'records;
+                            }
+                            std::thread::sleep(wait.min(Listener::CHECK_INTERVAL));
+                        }
+                    }
+                    Pace::Step(steps) => loop {
+                        match steps.recv_timeout(Listener::CHECK_INTERVAL) {
+                            Ok(()) => break,
+                            Err(RecvTimeoutError::Timeout) if running.get() => {}
+                            Err(..) => break 'records,
+                        }
+                    }
+                }
+
+                handler.handle(record.freed, None, &mut None);
+            }
+            None
+        }));
+
+        Ok(self)
+    }
+
+    #[allow(unused)]
+    pub fn set_freed_data(&mut self, freed: FreeD) {
+        let sample = Sample { freed, received: Instant::now() };
+        let pose = Pose::from(&sample);
+        self.history.lock().unwrap().push(sample);
+        self.subscribers.publish(&pose);
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The address FreeD is received on
+    pub fn address(&self) -> SocketAddr {
+        self.address
+    }
+
+    /// The last received pose, if any
+    pub fn pose(&self) -> Option<Pose> {
+        self.history.lock().unwrap().latest().map(Pose::from)
+    }
+
+    /// Where the camera is, in metres, or zeros if nothing was received yet
+    pub fn position(&self) -> (f32, f32, f32) {
+        self.pose().map_or((0.0, 0.0, 0.0), |pose| pose.position)
+    }
+
+    /// The pose at `time`, interpolated between the received samples, or
+    /// zeros if nothing was received yet. Lets renders line up with the time
+    /// of a video frame instead of with whatever arrived last.
+    pub fn pose_at(&self, time: Instant) -> Pose {
+        let freed = self.history.lock().unwrap().at(time).unwrap_or(FreeD::zero());
+        Pose::new(&freed, time)
+    }
+
+    /// The pose predicted for the prediction horizon from now, or zeros if
+    /// nothing was received yet. Stale samples are not extrapolated any
+    /// further than fresh ones.
+    pub fn predicted(&self) -> Pose {
+        let now = Instant::now();
+        let time = now.checked_add(self.prediction).unwrap_or(now);
+        let freed = self.history.lock().unwrap().predict(time, self.prediction_limit()).unwrap_or(FreeD::zero());
+        Pose::new(&freed, time)
+    }
+
+    /// The pose to render now: from the delay ago, interpolated, or
+    /// predicted ahead if the prediction is larger. Like in
+    /// [`Ptz::predicted`], stale samples are not extrapolated any further
+    /// than fresh ones, so a camera that stops sending doesn't drift away.
+    /// Zeros if nothing was received yet.
+    pub fn aligned(&self) -> Pose {
+        let now = Instant::now();
+        let time = now.checked_add(self.prediction)
+            .and_then(|time| time.checked_sub(self.delay))
+            .unwrap_or(now);
+        let freed = self.history.lock().unwrap().predict(time, self.prediction_limit()).unwrap_or(FreeD::zero());
+        Pose::new(&freed, time)
+    }
+
+    /// How far past the latest sample to extrapolate at most. The latest
+    /// sample is up to [`Timeouts::stale`] old while the camera is tracking,
+    /// and the full prediction horizon should still be reached then.
+    fn prediction_limit(&self) -> Duration {
+        self.prediction.saturating_add(self.timeouts.stale)
+    }
+
+    /// How long ago the last FreeD arrived
+    pub fn age(&self) -> Option<Duration> {
+        self.history.lock().unwrap().latest().map(|sample| sample.received.elapsed())
+    }
+
+    pub fn tracking_state(&self) -> TrackingState {
+        self.timeouts.state(self.age())
+    }
+}
+
+/// Listens for FreeD from several PTZs on a single UDP port, and routes each
+/// message to a PTZ by its camera ID. Useful because many PTZs send to the
+/// same port by default.
+pub struct PtzGroup {
+    address: SocketAddr,
+    ptzs: HashMap<u8, Route>,
+    /// For the socket as a whole. Messages are also counted for the PTZ they
+    /// are routed to, but anything invalid is only counted here.
+    statistics: Arc<Statistics>,
+}
+
+/// Where a [`PtzGroup`] puts the messages of one camera
+struct Route {
+    senders: Arc<SenderFilter>,
+    statistics: Arc<Statistics>,
+    handler: Handler,
+}
+
+impl PtzGroup {
+    pub fn new(address: SocketAddr) -> Self {
+        PtzGroup {
+            address,
+            ptzs: HashMap::new(),
+            statistics: Arc::new(Statistics::default()),
+        }
+    }
+
+    /// Route messages with the given camera ID to `ptz`, whatever its own
+    /// camera ID. Its senders and transform are applied; it doesn't poll,
+    /// record or forward.
+    pub fn add(mut self, camera_id: u8, ptz: &Ptz) -> Self {
+        self.ptzs.insert(camera_id, Route {
+            senders: ptz.senders.clone(),
+            statistics: ptz.statistics.clone(),
+            handler: Handler::routed(ptz),
+        });
+        self
+    }
+
+    /// What is received on the shared port, from all PTZs together. Only
+    /// here are packets and errors counted.
+    pub fn statistics(&self) -> Arc<Statistics> {
+        self.statistics.clone()
+    }
+
+    /// Receive FreeD until `running` is cleared, or the returned listener is
+    /// stopped or dropped. Fails if the address can't be bound.
+    pub fn start_listening(self, running: Arc<AtomicBool>) -> std::io::Result<Listener> {
+        let socket = UdpSocket::bind(self.address)?;
+        socket.set_read_timeout(Some(Listener::CHECK_INTERVAL))?;
+        let address = socket.local_addr()?;
+
+        Ok(Listener::spawn(running, Some(address), move |running| {
+            // Only report each unknown camera once, instead of 50 times a second
+            let mut unknown_ids = HashSet::new();
+            let mut ptzs = self.ptzs;
+
+            receive_loop(&socket, &running, &SenderFilter::default(), &self.statistics, None, None, |freed, source| {
+                match ptzs.get_mut(&freed.camera_id) {
+                    Some(route) => if route.senders.accept(source) {
+                        route.statistics.message(Instant::now());
+                        route.handler.handle(freed, Some(source), &mut None);
+                    }
+                    None => if unknown_ids.insert(freed.camera_id) {
+                        println!("Ignoring FreeD from unknown camera ID {} ({source})", freed.camera_id);
+                    }
+                }
+            });
+            None
+        }))
+    }
+}
+
+/// Receive and decode FreeD messages until `running` is cleared. The socket
+/// needs a read timeout, to check that regularly. If a poll address is given,
+/// the camera there is polled for every position update. See
+/// [`receive_datagram`] for what is done with what is received.
+fn receive_loop(
+    socket: &UdpSocket,
+    running: &Running,
+    senders: &SenderFilter,
+    statistics: &Statistics,
+    poll_address: Option<SocketAddr>,
+    forwarder: Option<&Forwarder>,
+    mut on_freed: impl FnMut(FreeD, SocketAddr),
+) {
+    // Room for a full ethernet frame, which may hold several FreeD messages
+    let mut buf = [0u8; 1500];
+    let poll = Message::poll(BROADCAST_CAMERA_ID).encode();
+    let mut last_poll = Instant::now() - Ptz::POLL_INTERVAL;
+
+    while running.get() {
+        if let Some(address) = poll_address {
+            if last_poll.elapsed() >= Ptz::POLL_INTERVAL {
+                if let Err(e) = socket.send_to(&poll, address) {
+                    poll_failed(&e, address, statistics);
+                }
+                last_poll = Instant::now();
+            }
+        }
+
+        match socket.recv_from(&mut buf) {
+            Ok((amount, source)) => receive_datagram(&buf[..amount], source, senders, statistics, forwarder, &mut on_freed),
+            Err(e) => receive_failed(&e, statistics),
+        }
+    }
+}
+
+/// Handle a received datagram: drop it if the sender is not allowed, forward
+/// it, and decode it. Everything in it is counted in `statistics`, and position
+/// messages are passed to `on_freed`.
+fn receive_datagram(
+    datagram: &[u8],
+    source: SocketAddr,
+    senders: &SenderFilter,
+    statistics: &Statistics,
+    forwarder: Option<&Forwarder>,
+    on_freed: &mut impl FnMut(FreeD, SocketAddr),
+) {
+    if !senders.accept(source) {
+        return
+    }
+    if statistics.packet() {
+        println!("Receiving FreeD data works again");
+    }
+    if let Some(forwarder) = forwarder {
+        forwarder.forward(datagram);
+    }
+
+    // A datagram may contain several messages, but never only part of one
+    let mut decoder = Decoder::new();
+    decoder.push(datagram);
+    for result in decoder.by_ref() {
+        match result {
+            Ok(Message::Position(freed)) => {
+                statistics.message(Instant::now());
+                on_freed(freed, source);
+            }
+            Ok(..) => statistics.other_message(),
+            Err(e) => statistics.error(&e),
+        }
+    }
+    if let Some(e) = decoder.flush() {
+        statistics.error(&e);
+    }
+}
+
+/// Count a failed receive, and report it if the previous one worked. Timeouts
+/// don't count: they are only there to check whether to stop.
+fn receive_failed(error: &std::io::Error, statistics: &Statistics) {
+    if matches!(error.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) {
+        return
+    }
+    if statistics.socket_error() {
+        println!("Error receiving FreeD data: {error}");
+    }
+}
+
+fn poll_failed(error: &std::io::Error, address: SocketAddr, statistics: &Statistics) {
+    if statistics.socket_error() {
+        println!("Error polling {address}: {error}");
+    }
+}
+
+/// Receive and decode FreeD from a byte stream until `running` is cleared.
+/// Messages can be split over several reads, so the decoder is never flushed.
+fn serial_loop(port: &mut impl Read, running: &Running, path: &str, statistics: &Statistics, mut on_freed: impl FnMut(FreeD)) {
+    let mut buf = [0u8; 256];
+    let mut decoder = Decoder::new();
+
+    while running.get() {
+        match port.read(&mut buf) {
+            Ok(amount) => {
+                if statistics.packet() {
+                    println!("Reading FreeD from {path} works again");
+                }
+                decoder.push(&buf[..amount]);
+                for result in decoder.by_ref() {
+                    match result {
+                        Ok(Message::Position(freed)) => {
+                            statistics.message(Instant::now());
+                            on_freed(freed);
+                        }
+                        Ok(..) => statistics.other_message(),
+                        Err(e) => statistics.error(&e),
+                    }
+                }
+            }
+            Err(e) if e.kind() == ErrorKind::TimedOut => {}
+            Err(e) => if statistics.socket_error() {
+                println!("Error reading FreeD from {path}: {e}");
+            }
+        }
+    }
+}
+
+/// A PTZ on a free port on localhost, so tests don't take the ports of the
+/// PTZs of LVC, or each other's
+#[cfg(test)]
+fn test_config() -> PtzConfig {
+    PtzConfig { bind: [127, 0, 0, 1].into(), port: 0, ..PtzConfig::lvc(1) }
+}
+
+/// How long tests wait for something to be received, at most
+#[cfg(test)]
+const RECEIVE_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// A socket on a free port on localhost, which waits at most
+/// [`RECEIVE_TIMEOUT`] for datagrams
+#[cfg(test)]
+fn test_socket() -> UdpSocket {
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    socket.set_read_timeout(Some(RECEIVE_TIMEOUT)).unwrap();
+    socket
+}
+
+/// The next datagram that arrives on `socket`
+#[cfg(test)]
+fn receive(socket: &UdpSocket) -> Vec<u8> {
+    let mut buf = [0u8; 64];
+    let amount = socket.recv(&mut buf).unwrap();
+    buf[..amount].to_vec()
+}
+
+#[cfg(test)]
+fn freed_with_pan(pan: f32) -> FreeD {
+    let mut freed = FreeD::zero();
+    freed.pan = pan;
+    freed
+}
+
+/// A PTZ listening over UDP, with a socket to send it FreeD from. It stops
+/// listening when dropped, also when a test fails.
+#[cfg(test)]
+struct TestPtz {
+    ptz: Ptz,
+    running: Arc<AtomicBool>,
+    socket: UdpSocket,
+}
+
+#[cfg(test)]
+impl TestPtz {
+    fn start(ptz: Ptz) -> Self {
+        let running = Arc::new(AtomicBool::new(true));
+        let ptz = ptz.start_listening(running.clone()).unwrap();
+        TestPtz { ptz, running, socket: test_socket() }
+    }
+
+    fn send(&self, datagram: &[u8]) {
+        self.socket.send_to(datagram, self.ptz.address()).unwrap();
+    }
+}
+
+#[cfg(test)]
+impl std::ops::Deref for TestPtz {
+    type Target = Ptz;
+
+    fn deref(&self) -> &Ptz {
+        &self.ptz
+    }
+}
+
+#[cfg(test)]
+impl std::ops::DerefMut for TestPtz {
+    fn deref_mut(&mut self) -> &mut Ptz {
+        &mut self.ptz
+    }
+}
+
+#[cfg(test)]
+impl Drop for TestPtz {
+    fn drop(&mut self) {
+        self.running.store(false, std::sync::atomic::Ordering::Relaxed);
+    }
+}
+
+#[test]
+fn test_group_routes_by_camera_id() {
+    use std::sync::atomic::Ordering;
+
+    let running = Arc::new(AtomicBool::new(true));
+    let ptz1 = Ptz::new(1);
+    let ptz2 = Ptz::new(2);
+    let (poses1, poses2) = (ptz1.subscribe(), ptz2.subscribe());
+    let listener = PtzGroup::new(test_config().address())
+        .add(1, &ptz1)
+        .add(2, &ptz2)
+        .start_listening(running.clone())
+        .unwrap();
+
+    let socket = test_socket();
+    for (camera_id, pan) in [(3, 30.0), (1, 10.0), (2, 20.0)] {
+        let mut freed = freed_with_pan(pan);
+        freed.camera_id = camera_id;
+        socket.send_to(&freed.encode(), listener.address().unwrap()).unwrap();
+    }
+
+    assert_eq!(poses1.recv_timeout(RECEIVE_TIMEOUT).unwrap().pan, 10.0);
+    assert_eq!(poses2.recv_timeout(RECEIVE_TIMEOUT).unwrap().pan, 20.0);
+    assert_eq!(ptz1.stats().messages, 1);
+    running.store(false, Ordering::Relaxed);
+}
+
+#[test]
+fn test_polled() {
+    let camera = test_socket();
+    let ptz = TestPtz::start(Ptz::from_config(&test_config()).polled(camera.local_addr().unwrap()));
+    let poses = ptz.subscribe();
+
+    // Answer a single poll, like a camera in polled mode would
+    let mut buf = [0u8; 64];
+    let (amount, source) = camera.recv_from(&mut buf).unwrap();
+    assert_eq!(Message::try_from(&buf[..amount]), Ok(Message::poll(BROADCAST_CAMERA_ID)));
+    camera.send_to(&freed_with_pan(42.0).encode(), source).unwrap();
+
+    assert_eq!(poses.recv_timeout(RECEIVE_TIMEOUT).unwrap().pan, 42.0);
+}
+
+#[test]
+fn test_concatenated_datagram() {
+    let ptz = TestPtz::start(Ptz::from_config(&test_config()));
+    let poses = ptz.subscribe();
+
+    let mut freed = FreeD::zero();
+    freed.zoom = 1000;
+    let mut datagram = freed.encode().to_vec();
+    freed.zoom = 2000;
+    datagram.extend(freed.encode());
+    ptz.send(&datagram);
+
+    for zoom in [1000, 2000] {
+        assert_eq!(poses.recv_timeout(RECEIVE_TIMEOUT).unwrap().zoom, zoom);
+    }
+}
+
+#[cfg(unix)]
+#[test]
+fn test_serial() {
+    use std::io::Write;
+    use std::sync::atomic::Ordering;
+    use serialport::SerialPort;
+
+    let running = Arc::new(AtomicBool::new(true));
+    let (mut master, slave) = serialport::TTYPort::pair().unwrap();
+    let config = PtzConfig { serial: slave.name(), ..PtzConfig::lvc(5) };
+    let ptz = Ptz::from_config(&config).start(&config, running.clone()).unwrap();
+    let poses = ptz.subscribe();
+
+    let mut freed = FreeD::zero();
+    freed.tilt = -12.5;
+    let bytes = freed.encode();
+    // Garbage first, and the message in two parts
+    master.write_all(&[0x00, 0xD1, 0x12]).unwrap();
+    master.write_all(&bytes[..15]).unwrap();
+    master.flush().unwrap();
+    std::thread::sleep(Duration::from_millis(20));
+    master.write_all(&bytes[15..]).unwrap();
+    master.flush().unwrap();
+
+    assert_eq!(poses.recv_timeout(RECEIVE_TIMEOUT).unwrap().tilt, -12.5);
+    running.store(false, Ordering::Relaxed);
+
+    // The error says which port
+    let config = PtzConfig { serial: Some("/dev/no-such-tty".to_string()), ..PtzConfig::lvc(5) };
+    match Ptz::from_config(&config).start(&config, running) {
+        Ok(..) => panic!("opened a serial port that doesn't exist"),
+        Err(e) => assert!(e.to_string().starts_with("/dev/no-such-tty: "), "{e}"),
+    }
+}
+
+#[test]
+fn test_replay_step() {
+    use std::sync::atomic::Ordering;
+
+    let running = Arc::new(AtomicBool::new(true));
+    let records: Vec<_> = [10.0, 20.0].into_iter().map(|pan| {
+        Ok(Record { time: std::time::SystemTime::now(), source: None, freed: freed_with_pan(pan) })
+    }).collect();
+    let (step, steps) = std::sync::mpsc::channel();
+    let ptz = Ptz::new(6).start_replay(records.into_iter(), Pace::Step(steps), running.clone()).unwrap();
+    let poses = ptz.subscribe();
+
+    for pan in [10.0, 20.0] {
+        step.send(()).unwrap();
+        assert_eq!(poses.recv_timeout(RECEIVE_TIMEOUT).unwrap().pan, pan);
+        assert_eq!(ptz.pose().unwrap().pan, pan);
+    }
+    running.store(false, Ordering::Relaxed);
+}
+
+#[test]
+fn test_replay_speed() {
+    let running = Arc::new(AtomicBool::new(true));
+    for speed in [0.0, -1.0, f32::NAN, f32::INFINITY] {
+        let replay = Ptz::new(6).start_replay(std::iter::empty(), Pace::Speed(speed), running.clone());
+        assert_eq!(replay.err().map(|e| e.kind()), Some(ErrorKind::InvalidInput), "{speed}");
+    }
+
+    // So slow that the second record never comes, but it can still be stopped
+    let records = [0, 1].map(|secs| Ok(Record {
+        time: std::time::UNIX_EPOCH + Duration::from_secs(secs),
+        source: None,
+        freed: FreeD::zero(),
+    }));
+    let ptz = Ptz::new(6);
+    let poses = ptz.subscribe();
+    let mut ptz = ptz.start_replay(records.into_iter(), Pace::Speed(1e-30), running.clone()).unwrap();
+    assert!(poses.recv_timeout(RECEIVE_TIMEOUT).is_ok());
+    assert!(ptz.is_listening());
+    ptz.stop();
+    assert!(poses.try_recv().is_err());
+}
+
+#[test]
+fn test_start_recording_and_replay() {
+    use std::sync::atomic::Ordering;
+
+    let path = std::env::temp_dir().join(format!("test_start_recording_and_replay_{}.freed", std::process::id()));
+    let running = Arc::new(AtomicBool::new(true));
+    let config = PtzConfig { record: Some(path.clone()), ..test_config() };
+    let ptz = Ptz::from_config(&config).start(&config, running.clone()).unwrap();
+    let poses = ptz.subscribe();
+    test_socket().send_to(&freed_with_pan(10.0).encode(), ptz.address()).unwrap();
+    poses.recv_timeout(RECEIVE_TIMEOUT).unwrap();
+    // Which closes the recording
+    drop(ptz);
+
+    let config = PtzConfig { replay: Some(path.clone()), ..test_config() };
+    let ptz = Ptz::from_config(&config);
+    let poses = ptz.subscribe();
+    let _ptz = ptz.start(&config, running.clone()).unwrap();
+    assert_eq!(poses.recv_timeout(RECEIVE_TIMEOUT).unwrap().pan, 10.0);
+    running.store(false, Ordering::Relaxed);
+    std::fs::remove_file(&path).unwrap();
+
+    let error = Ptz::from_config(&config).start(&config, running).err().unwrap();
+    assert!(error.to_string().contains(path.to_str().unwrap()), "{error}");
+}
+
+#[test]
+fn test_camera_id() {
+    let destination = test_socket();
+    let forwarder = Forwarder::new([destination.local_addr().unwrap()]).unwrap();
+    let config = PtzConfig { camera_id: Some(2), ..test_config() };
+    let ptz = TestPtz::start(Ptz::from_config(&config).forwarding(forwarder));
+    let poses = ptz.subscribe();
+
+    // Both in one datagram, so only the message of camera 2 can be forwarded
+    let mut datagram = Vec::new();
+    for (camera_id, pan) in [(1, 10.0), (2, 20.0)] {
+        let mut freed = freed_with_pan(pan);
+        freed.camera_id = camera_id;
+        datagram.extend(freed.encode());
+    }
+    ptz.send(&datagram);
+
+    assert_eq!(FreeD::try_from(&receive(&destination)[..]).unwrap().pan, 20.0);
+    assert_eq!(poses.recv_timeout(RECEIVE_TIMEOUT).unwrap().pan, 20.0);
+    assert!(poses.try_recv().is_err());
+}
+
+#[test]
+fn test_forwarding() {
+    let destinations = [test_socket(), test_socket()];
+    let forwarder = Forwarder::new(destinations.iter().map(|d| d.local_addr().unwrap())).unwrap();
+    let ptz = TestPtz::start(Ptz::from_config(&test_config()).forwarding(forwarder));
+
+    // Forwarded as is, even if it is not valid FreeD
+    let datagram = [0xD1, 0x01, 0x02];
+    ptz.send(&datagram);
+    for destination in &destinations {
+        assert_eq!(receive(destination), datagram);
+    }
+
+    assert!(ptz.forwarder().unwrap().errors().iter().all(|(_, errors)| *errors == 0));
+}
+
+#[test]
+fn test_transform_and_forward() {
+    let destination = test_socket();
+    let forwarder = Forwarder::new([destination.local_addr().unwrap()]).unwrap();
+    let config = PtzConfig {
+        transform: Some(Transform { pan_offset: -10.0, ..Transform::default() }),
+        ..test_config()
+    };
+    let ptz = TestPtz::start(Ptz::from_config(&config).forwarding(forwarder));
+    let poses = ptz.subscribe();
+
+    ptz.send(&freed_with_pan(25.0).encode());
+
+    assert_eq!(FreeD::try_from(&receive(&destination)[..]).unwrap().pan, 15.0);
+    assert_eq!(poses.recv_timeout(RECEIVE_TIMEOUT).unwrap().pan, 15.0);
+}
+
+#[test]
+fn test_allowed_senders() {
+    let config = PtzConfig {
+        senders: vec![[127, 0, 0, 2].into(), [127, 0, 0, 3].into()],
+        ..test_config()
+    };
+    let ptz = TestPtz::start(Ptz::from_config(&config));
+    let poses = ptz.subscribe();
+
+    for (sender, pan) in [([127, 0, 0, 1], 10.0), ([127, 0, 0, 2], 20.0), ([127, 0, 0, 1], 40.0), ([127, 0, 0, 3], 30.0)] {
+        UdpSocket::bind(SocketAddr::from((sender, 0))).unwrap().send_to(&freed_with_pan(pan).encode(), ptz.address()).unwrap();
+    }
+
+    for pan in [20.0, 30.0] {
+        assert_eq!(poses.recv_timeout(RECEIVE_TIMEOUT).unwrap().pan, pan);
+    }
+    assert_eq!(ptz.senders().rejected(), vec![([127, 0, 0, 1].into(), 2)]);
+}
+
+#[test]
+fn test_tracking_state() {
+    let timeouts = Timeouts { stale: Duration::from_secs(1), lost: Duration::from_secs(2) };
+    let ptz = Ptz::new(7).timeouts(timeouts);
+    assert_eq!((ptz.age(), ptz.tracking_state()), (None, TrackingState::Lost));
+
+    // Newer and newer samples, so the latest one gets younger
+    let now = Instant::now();
+    for (age, state) in [(2500, TrackingState::Lost), (1500, TrackingState::Stale), (0, TrackingState::Ok)] {
+        ptz.history.lock().unwrap().push(Sample { freed: FreeD::zero(), received: now - Duration::from_millis(age) });
+        assert_eq!(ptz.tracking_state(), state);
+    }
+}
+
+#[test]
+fn test_delay() {
+    let ptz = Ptz::new(8).delayed(Duration::from_millis(500));
+    let now = Instant::now();
+    for (pan, age) in [(10.0, 1000), (20.0, 0)] {
+        ptz.history.lock().unwrap().push(Sample { freed: freed_with_pan(pan), received: now - Duration::from_millis(age) });
+    }
+
+    // 500 ms ago is between the two samples
+    let pan = ptz.aligned().pan;
+    assert!(pan > 10.0 && pan < 20.0, "{pan}");
+    assert_eq!(ptz.pose().unwrap().pan, 20.0);
+    let time = now - Duration::from_millis(250);
+    let pose = ptz.pose_at(time);
+    assert_eq!((pose.pan, pose.time), (17.5, time));
+}
+
+#[test]
+fn test_long_delay() {
+    // Close to the longest delay, which is more than the default history
+    // holds at 50 Hz
+    let config = PtzConfig { delay_ms: Some(9500), frame_rate: 50.0, ..PtzConfig::lvc(1) };
+    let ptz = Ptz::from_config(&config);
+    let now = Instant::now();
+    for age in (0..=10_000).rev().step_by(20) {
+        let freed = freed_with_pan(age as f32 / 100.0);
+        ptz.history.lock().unwrap().push(Sample { freed, received: now - Duration::from_millis(age) });
+    }
+
+    let pan = ptz.aligned().pan;
+    assert!(pan > 94.9 && pan <= 95.0, "{pan}");
+}
+
+#[test]
+fn test_prediction_from_older_sample() {
+    let ptz = Ptz::new(1).predicting(Duration::from_millis(50));
+    let now = Instant::now();
+    for (pan, age) in [(0.0, 40), (10.0, 20)] {
+        ptz.history.lock().unwrap().push(Sample { freed: freed_with_pan(pan), received: now - Duration::from_millis(age) });
+    }
+
+    // 500°/s, for the 20 ms since the latest sample plus the full 50 ms
+    // horizon
+    for pan in [ptz.predicted().pan, ptz.aligned().pan] {
+        assert!(pan > 44.9 && pan < 50.0, "{pan}");
+    }
+}
+
+#[test]
+fn test_stats() {
+    let ptz = TestPtz::start(Ptz::from_config(&test_config()));
+    let poses = ptz.subscribe();
+
+    let mut corrupt = FreeD::zero().encode();
+    corrupt[28] ^= 0xFF;
+    let datagrams = [
+        &FreeD::zero().encode()[..],
+        &corrupt[..],
+        &FreeD::zero().encode()[..20],
+        &[0x42, 0x00],
+        &Message::poll(1).encode()[..],
+        // Once this one is received, so are the others
+        &FreeD::zero().encode()[..],
+    ];
+    for datagram in datagrams {
+        ptz.send(datagram);
+    }
+    for _ in 0..2 {
+        poses.recv_timeout(RECEIVE_TIMEOUT).unwrap();
+    }
+
+    let stats = ptz.stats();
+    assert_eq!((stats.packets, stats.messages, stats.other_messages), (6, 2, 1));
+    assert_eq!((stats.checksum_errors, stats.wrong_length, stats.unknown_type), (1, 1, 1));
+    assert_eq!(stats.socket_errors, 0);
+}
+
+#[test]
+fn test_lifecycle() {
+    use std::sync::atomic::Ordering;
+
+    let any_port = test_config();
+    let mut ptz = TestPtz::start(Ptz::from_config(&any_port));
+    let first = ptz.address();
+    assert_ne!(first.port(), 0);
+
+    // The port is taken
+    let taken = PtzConfig { port: first.port(), ..any_port.clone() };
+    assert!(Ptz::from_config(&taken).start_listening(ptz.running.clone()).is_err());
+
+    // To the addresses it listened on before as well, so not with ptz.send
+    let poses = ptz.subscribe();
+    let socket = test_socket();
+    let send = |pan: f32, address: SocketAddr| {
+        socket.send_to(&freed_with_pan(pan).encode(), address).unwrap();
+    };
+    send(10.0, first);
+    assert_eq!(poses.recv_timeout(RECEIVE_TIMEOUT).unwrap().pan, 10.0);
+
+    // Moved to another port, and the first one is free again
+    ptz.rebind(SocketAddr::from(([127, 0, 0, 1], 0))).unwrap();
+    let second = ptz.address();
+    assert_ne!(second, first);
+    UdpSocket::bind(first).unwrap();
+    send(20.0, second);
+    assert_eq!(poses.recv_timeout(RECEIVE_TIMEOUT).unwrap().pan, 20.0);
+
+    // Moved to all interfaces on the same port, and back if that fails
+    ptz.rebind(SocketAddr::from(([0, 0, 0, 0], second.port()))).unwrap();
+    let all = ptz.address();
+    assert_eq!(all.port(), second.port());
+    send(21.0, second);
+    assert_eq!(poses.recv_timeout(RECEIVE_TIMEOUT).unwrap().pan, 21.0);
+    assert!(ptz.rebind(SocketAddr::from(([192, 0, 2, 1], second.port()))).is_err());
+    assert!(ptz.is_listening());
+    assert_eq!(ptz.address(), all);
+    send(20.0, second);
+    assert_eq!(poses.recv_timeout(RECEIVE_TIMEOUT).unwrap().pan, 20.0);
+
+    // Stopped in time, and nothing is received afterwards
+    let stop = Instant::now();
+    ptz.stop();
+    assert!(stop.elapsed() <= Listener::CHECK_INTERVAL + Duration::from_millis(50));
+    assert!(!ptz.is_listening());
+    send(30.0, second);
+    assert!(poses.recv_timeout(Duration::from_millis(50)).is_err());
+    assert_eq!(ptz.pose().unwrap().pan, 20.0);
+
+    // Also when `running` is cleared
+    let ptz = TestPtz::start(Ptz::from_config(&any_port));
+    assert!(ptz.is_listening());
+    ptz.running.store(false, Ordering::Relaxed);
+    std::thread::sleep(Listener::CHECK_INTERVAL + Duration::from_millis(50));
+    assert!(!ptz.is_listening());
+}
+
+#[test]
+fn test_subscribe() {
+    let ptz = TestPtz::start(Ptz::from_config(&test_config()));
+    let poses = ptz.subscribe();
+    let (sender, zooms) = std::sync::mpsc::channel();
+    let _subscription = ptz.on_pose(move |pose| sender.send(pose.zoom).unwrap());
+
+    let sent = Instant::now();
+    for zoom in [1000, 2000] {
+        let mut freed = FreeD::zero();
+        freed.zoom = zoom;
+        ptz.send(&freed.encode());
+    }
+
+    for zoom in [1000, 2000] {
+        let pose = poses.recv_timeout(RECEIVE_TIMEOUT).unwrap();
+        assert_eq!(pose.zoom, zoom);
+        assert!(pose.time >= sent);
+        assert_eq!(zooms.recv_timeout(RECEIVE_TIMEOUT), Ok(zoom));
+    }
+}
@@ -0,0 +1,47 @@
+use std::time::Instant;
+use crate::freed::FreeD;
+use super::Sample;
+
+/// Where a camera is and where it looks, at one moment. What the tracking side
+/// hands to whatever renders or forwards it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pose {
+    pub camera_id: u8,
+    /// In degrees, positive to the right
+    pub pan: f32,
+    /// In degrees, positive up
+    pub tilt: f32,
+    /// In degrees
+    pub roll: f32,
+    /// In metres, where X and Y are horizontal, Y is straight ahead and Z
+    /// points up
+    pub position: (f32, f32, f32),
+    /// Raw value, see [`crate::lens::LensProfile`]
+    pub zoom: u32,
+    /// Raw value, see [`crate::lens::LensProfile`]
+    pub focus: u32,
+    /// When the FreeD was received. For interpolated or predicted poses, the
+    /// moment they are for.
+    pub time: Instant,
+}
+
+impl Pose {
+    pub fn new(freed: &FreeD, time: Instant) -> Self {
+        Pose {
+            camera_id: freed.camera_id,
+            pan: freed.pan,
+            tilt: freed.tilt,
+            roll: freed.roll,
+            position: freed.position,
+            zoom: freed.zoom,
+            focus: freed.focus,
+            time,
+        }
+    }
+}
+
+impl From<&Sample> for Pose {
+    fn from(sample: &Sample) -> Self {
+        Pose::new(&sample.freed, sample.received)
+    }
+}
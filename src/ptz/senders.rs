@@ -0,0 +1,71 @@
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Mutex;
+
+/// Which hosts may send FreeD to a PTZ, and which others tried to. Without
+/// this, a camera sending to the wrong port moves the overlay of another one.
+#[derive(Debug, Default)]
+pub struct SenderFilter {
+    /// Accept everything if empty
+    allowed: Vec<IpAddr>,
+    /// Number of rejected datagrams per sender
+    rejected: Mutex<HashMap<IpAddr, u64>>,
+}
+
+impl SenderFilter {
+    pub fn new(allowed: impl IntoIterator<Item = IpAddr>) -> Self {
+        SenderFilter {
+            allowed: allowed.into_iter().collect(),
+            rejected: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn allowed(&self) -> &[IpAddr] {
+        &self.allowed
+    }
+
+    /// Whether to accept a datagram from `source`. Rejected datagrams are
+    /// counted, and the first one of every sender is logged.
+    pub(crate) fn accept(&self, source: SocketAddr) -> bool {
+        // IPv4 senders show up as mapped addresses on a dual-stack socket
+        let ip = source.ip().to_canonical();
+        if self.allowed.is_empty() || self.allowed.contains(&ip) {
+            return true
+        }
+
+        let mut rejected = self.rejected.lock().unwrap();
+        let count = rejected.entry(ip).or_default();
+        if *count == 0 {
+            println!("Ignoring FreeD from unexpected sender {source}");
+        }
+        *count += 1;
+
+        false
+    }
+
+    /// The senders that were rejected, and how many datagrams each sent,
+    /// most first
+    pub fn rejected(&self) -> Vec<(IpAddr, u64)> {
+        let mut rejected: Vec<_> = self.rejected.lock().unwrap().iter().map(|(ip, count)| (*ip, *count)).collect();
+        rejected.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        rejected
+    }
+}
+
+#[test]
+fn test_filter() {
+    let everyone = SenderFilter::default();
+    assert!(everyone.accept("10.0.0.1:1234".parse().unwrap()));
+
+    let filter = SenderFilter::new(["10.0.0.1".parse().unwrap(), "10.0.0.2".parse().unwrap()]);
+    assert!(filter.accept("10.0.0.1:1234".parse().unwrap()));
+    assert!(filter.accept("[::ffff:10.0.0.2]:1234".parse().unwrap()));
+    assert!(!filter.accept("10.0.0.3:1234".parse().unwrap()));
+    assert!(!filter.accept("10.0.0.4:1234".parse().unwrap()));
+    assert!(!filter.accept("10.0.0.4:5678".parse().unwrap()));
+
+    assert_eq!(filter.rejected(), vec![
+        ("10.0.0.4".parse().unwrap(), 2),
+        ("10.0.0.3".parse().unwrap(), 1),
+    ]);
+}
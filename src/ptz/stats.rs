@@ -0,0 +1,167 @@
+use std::fmt::{Display, Formatter};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use crate::freed::ParseError;
+
+/// Counts what a FreeD listener receives, to tell whether the tracking feed of
+/// a camera is healthy. Shared between the listening thread and whoever wants
+/// to know.
+///
+/// Packets and errors are counted per socket. When several PTZs share one,
+/// through a [`PtzGroup`](super::PtzGroup), each PTZ only counts its own
+/// position messages, and the rest is in the statistics of the group.
+#[derive(Debug, Default)]
+pub struct Statistics {
+    counters: Mutex<Counters>,
+}
+
+/// A snapshot of [`Statistics`]
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Stats {
+    /// UDP datagrams, or reads from a serial port
+    pub packets: u64,
+    /// Valid position (D1) messages
+    pub messages: u64,
+    pub checksum_errors: u64,
+    pub wrong_length: u64,
+    /// Unknown message types
+    pub unknown_type: u64,
+    /// Valid messages of another type than D1, e.g. status or polls
+    pub other_messages: u64,
+    /// Angles or commands out of range
+    pub invalid_values: u64,
+    /// Failed receives from the socket or serial port
+    pub socket_errors: u64,
+    /// Position messages per second, recently. Drops when they stop.
+    pub rate: f32,
+    /// How much the time between position messages varies, on average
+    pub jitter: Duration,
+}
+
+#[derive(Debug, Default)]
+struct Counters {
+    stats: Stats,
+    last_message: Option<Instant>,
+    /// Moving averages of the time between messages and of its deviation
+    interval: f32,
+    jitter: f32,
+    /// Whether the last receive failed, to report only changes
+    failing: bool,
+}
+
+impl Statistics {
+    /// Weight of a new interval in the moving averages, like RFC 3550 does
+    /// for jitter
+    const SMOOTHING: f32 = 1.0 / 16.0;
+
+    pub fn snapshot(&self) -> Stats {
+        let counters = self.counters.lock().unwrap();
+        let mut stats = counters.stats;
+        if let Some(last) = counters.last_message {
+            // Without new messages the rate should drop, instead of staying at
+            // what it was when they stopped
+            let interval = counters.interval.max(last.elapsed().as_secs_f32());
+            stats.rate = if interval > 0.0 { 1.0 / interval } else { 0.0 };
+        }
+        stats.jitter = Duration::from_secs_f32(counters.jitter);
+        stats
+    }
+
+    /// Returns whether receiving failed before this packet
+    pub(crate) fn packet(&self) -> bool {
+        let mut counters = self.counters.lock().unwrap();
+        counters.stats.packets += 1;
+        std::mem::replace(&mut counters.failing, false)
+    }
+
+    pub(crate) fn message(&self, time: Instant) {
+        let mut counters = self.counters.lock().unwrap();
+        counters.stats.messages += 1;
+        if let Some(last) = counters.last_message.replace(time) {
+            let interval = time.saturating_duration_since(last).as_secs_f32();
+            if counters.stats.messages == 2 {
+                counters.interval = interval;
+            }
+            let deviation = (interval - counters.interval).abs();
+            counters.interval += Self::SMOOTHING * (interval - counters.interval);
+            counters.jitter += Self::SMOOTHING * (deviation - counters.jitter);
+        }
+    }
+
+    pub(crate) fn other_message(&self) {
+        self.counters.lock().unwrap().stats.other_messages += 1;
+    }
+
+    pub(crate) fn error(&self, error: &ParseError) {
+        let stats = &mut self.counters.lock().unwrap().stats;
+        match error {
+            ParseError::WrongLength { .. } => stats.wrong_length += 1,
+            ParseError::UnknownMessageType(..) => stats.unknown_type += 1,
+            ParseError::ChecksumMismatch { .. } => stats.checksum_errors += 1,
+            ParseError::OutOfRange { .. } | ParseError::UnknownCommand(..) => stats.invalid_values += 1,
+        }
+    }
+
+    /// Returns whether this is the first error since the last packet, so it
+    /// can be reported once instead of every time
+    pub(crate) fn socket_error(&self) -> bool {
+        let mut counters = self.counters.lock().unwrap();
+        counters.stats.socket_errors += 1;
+        !std::mem::replace(&mut counters.failing, true)
+    }
+}
+
+impl Stats {
+    /// Everything that went wrong, together
+    pub fn errors(&self) -> u64 {
+        self.checksum_errors + self.wrong_length + self.unknown_type + self.invalid_values + self.socket_errors
+    }
+}
+
+impl Display for Stats {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} packets, {} messages ({:.1}/s), {} other messages, jitter {:.1} ms, {} checksum errors, {} wrong length, \
+             {} unknown type, {} invalid, {} socket errors",
+            self.packets,
+            self.messages,
+            self.rate,
+            self.other_messages,
+            self.jitter.as_secs_f32() * 1000.0,
+            self.checksum_errors,
+            self.wrong_length,
+            self.unknown_type,
+            self.invalid_values,
+            self.socket_errors,
+        )
+    }
+}
+
+#[test]
+fn test_statistics() {
+    let statistics = Statistics::default();
+    let start = Instant::now();
+    assert_eq!(statistics.snapshot(), Stats::default());
+
+    // 50 Hz, every other message 5 ms late
+    for i in 0..100 {
+        statistics.packet();
+        statistics.message(start + Duration::from_millis(20 * i + 5 * (i % 2)));
+    }
+    statistics.error(&ParseError::ChecksumMismatch { expected: 1, actual: 2 });
+    statistics.error(&ParseError::WrongLength { expected: 29, actual: 3 });
+    statistics.error(&ParseError::UnknownMessageType(0x42));
+
+    let stats = statistics.snapshot();
+    assert_eq!((stats.packets, stats.messages), (100, 100));
+    assert_eq!((stats.checksum_errors, stats.wrong_length, stats.unknown_type), (1, 1, 1));
+    assert!((stats.rate - 50.0).abs() < 1.0, "{}", stats.rate);
+    assert!(stats.jitter > Duration::from_millis(3), "{:?}", stats.jitter);
+
+    assert!(statistics.socket_error());
+    assert!(!statistics.socket_error());
+    assert!(statistics.packet());
+    assert_eq!(statistics.snapshot().socket_errors, 2);
+    assert_eq!(statistics.snapshot().errors(), 5);
+}
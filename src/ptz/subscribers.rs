@@ -0,0 +1,172 @@
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{sync_channel, Receiver, SyncSender, TrySendError};
+use super::Pose;
+
+type CallbackFn = Box<dyn FnMut(&Pose) + Send>;
+
+/// A callback, with whether it is still subscribed
+struct Callback {
+    call: Mutex<CallbackFn>,
+    subscribed: Arc<AtomicBool>,
+}
+
+/// Who gets every new pose of a PTZ, as soon as it is received, so they
+/// don't have to poll for it.
+#[derive(Default)]
+pub(crate) struct Subscribers {
+    channels: Mutex<Vec<SyncSender<Pose>>>,
+    #[cfg(feature = "tokio")]
+    streams: Mutex<Vec<tokio::sync::mpsc::Sender<Pose>>>,
+    callbacks: Mutex<Vec<Arc<Callback>>>,
+}
+
+impl Subscribers {
+    /// How many poses a channel holds before new ones are dropped, a few
+    /// seconds at 50 Hz
+    pub(crate) const CHANNEL_CAPACITY: usize = 256;
+
+    pub(crate) fn channel(&self) -> Receiver<Pose> {
+        let (sender, receiver) = sync_channel(Self::CHANNEL_CAPACITY);
+        self.channels.lock().unwrap().push(sender);
+        receiver
+    }
+
+    #[cfg(feature = "tokio")]
+    pub(crate) fn stream(&self) -> tokio::sync::mpsc::Receiver<Pose> {
+        let (sender, receiver) = tokio::sync::mpsc::channel(Self::CHANNEL_CAPACITY);
+        self.streams.lock().unwrap().push(sender);
+        receiver
+    }
+
+    pub(crate) fn callback(&self, callback: impl FnMut(&Pose) + Send + 'static) -> Subscription {
+        let subscribed = Arc::new(AtomicBool::new(true));
+        self.callbacks.lock().unwrap().push(Arc::new(Callback { call: Mutex::new(Box::new(callback)), subscribed: subscribed.clone() }));
+        Subscription { subscribed }
+    }
+
+    /// Send `pose` to every subscriber. A channel that is full misses it,
+    /// so a slow receiver doesn't hold up the others, and a channel whose
+    /// receiver is gone is removed, like a callback that was unsubscribed or
+    /// that panicked. Callbacks are called without holding any lock of the
+    /// subscribers, so they can subscribe and unsubscribe themselves.
+    pub(crate) fn publish(&self, pose: &Pose) {
+        self.channels.lock().unwrap().retain(|channel| {
+            !matches!(channel.try_send(pose.clone()), Err(TrySendError::Disconnected(..)))
+        });
+        #[cfg(feature = "tokio")]
+        self.streams.lock().unwrap().retain(|stream| {
+            use tokio::sync::mpsc::error::TrySendError;
+            !matches!(stream.try_send(pose.clone()), Err(TrySendError::Closed(..)))
+        });
+        let callbacks = {
+            let mut callbacks = self.callbacks.lock().unwrap();
+            callbacks.retain(|callback| callback.subscribed.load(Ordering::Relaxed));
+            callbacks.clone()
+        };
+        for callback in callbacks {
+            // Poisoned if it panicked while another thread called it
+            let Ok(mut call) = callback.call.lock() else { continue };
+            if !callback.subscribed.load(Ordering::Relaxed) {
+                continue
+            }
+            // A panic would stop the listener, so only the callback goes
+            if catch_unwind(AssertUnwindSafe(|| call(pose))).is_err() {
+                println!("A pose callback panicked, so it is unsubscribed");
+                callback.subscribed.store(false, Ordering::Relaxed);
+            }
+        }
+    }
+}
+
+/// Keeps a callback of [`Ptz::on_pose`](super::Ptz::on_pose) subscribed.
+/// Dropping it unsubscribes, though a call that has started already still
+/// finishes.
+#[must_use = "the callback is unsubscribed when this is dropped"]
+pub struct Subscription {
+    subscribed: Arc<AtomicBool>,
+}
+
+impl Drop for Subscription {
+    fn drop(&mut self) {
+        self.subscribed.store(false, Ordering::Relaxed);
+    }
+}
+
+#[test]
+fn test_publish() {
+    use std::time::Instant;
+    use crate::freed::FreeD;
+
+    let subscribers = Subscribers::default();
+    let first = subscribers.channel();
+    let second = subscribers.channel();
+    let pans = Arc::new(Mutex::new(Vec::new()));
+    let callback_pans = pans.clone();
+    let subscription = subscribers.callback(move |pose| callback_pans.lock().unwrap().push(pose.pan));
+    // Panics don't reach the listener, and don't stop other callbacks
+    let _panicking = subscribers.callback(|_| panic!("callback"));
+
+    let mut freed = FreeD::zero();
+    freed.pan = 10.0;
+    subscribers.publish(&Pose::new(&freed, Instant::now()));
+    drop(second);
+    subscribers.publish(&Pose::new(&FreeD::zero(), Instant::now()));
+
+    assert_eq!(first.try_iter().map(|pose| pose.pan).collect::<Vec<_>>(), [10.0, 0.0]);
+    assert_eq!(*pans.lock().unwrap(), [10.0, 0.0]);
+    assert_eq!(subscribers.channels.lock().unwrap().len(), 1);
+    assert_eq!(subscribers.callbacks.lock().unwrap().len(), 1);
+
+    drop(subscription);
+    subscribers.publish(&Pose::new(&FreeD::zero(), Instant::now()));
+    assert_eq!(pans.lock().unwrap().len(), 2);
+    assert!(subscribers.callbacks.lock().unwrap().is_empty());
+
+    // A full channel misses poses, instead of blocking
+    for _ in 0..Subscribers::CHANNEL_CAPACITY + 1 {
+        subscribers.publish(&Pose::new(&FreeD::zero(), Instant::now()));
+    }
+    assert_eq!(first.try_iter().count(), Subscribers::CHANNEL_CAPACITY);
+}
+
+#[test]
+fn test_resubscribe_from_callback() {
+    use std::sync::mpsc::channel;
+    use std::time::{Duration, Instant};
+    use crate::freed::FreeD;
+
+    // The first callback replaces itself by a second one, which is dropped
+    // again when the first one is called once more
+    let subscribers = Arc::new(Subscribers::default());
+    let (sender, pans) = channel();
+    let subscription = Arc::new(Mutex::new(None));
+    let first = {
+        let subscribers = subscribers.clone();
+        let subscription = subscription.clone();
+        subscribers.clone().callback(move |pose| {
+            let sender = sender.clone();
+            sender.send(("first", pose.pan)).unwrap();
+            *subscription.lock().unwrap() = Some(subscribers.callback(move |pose| sender.send(("second", pose.pan)).unwrap()));
+        })
+    };
+    *subscription.lock().unwrap() = Some(first);
+
+    // On another thread, so a deadlock fails the test instead of hanging it
+    let publisher = {
+        let subscribers = subscribers.clone();
+        std::thread::spawn(move || {
+            for pan in [10.0, 20.0] {
+                let mut freed = FreeD::zero();
+                freed.pan = pan;
+                subscribers.publish(&Pose::new(&freed, Instant::now()));
+            }
+        })
+    };
+    let timeout = Duration::from_secs(1);
+    assert_eq!(pans.recv_timeout(timeout), Ok(("first", 10.0)));
+    assert_eq!(pans.recv_timeout(timeout), Ok(("second", 20.0)));
+    publisher.join().unwrap();
+    assert!(pans.try_recv().is_err());
+}
@@ -0,0 +1,69 @@
+use std::fmt::{Display, Formatter};
+use std::time::{Duration, Instant};
+use crate::freed::FreeD;
+
+/// A FreeD message and when it was received
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sample {
+    pub freed: FreeD,
+    pub received: Instant,
+}
+
+/// Whether the FreeD of a camera can be trusted, based on how long ago the
+/// last message arrived.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrackingState {
+    /// Messages are coming in
+    Ok,
+    /// Some messages were missed. The camera may have moved since.
+    Stale,
+    /// No messages for a while, or never
+    Lost,
+}
+
+/// After how long without messages tracking becomes stale, and lost
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Timeouts {
+    pub stale: Duration,
+    pub lost: Duration,
+}
+
+impl Default for Timeouts {
+    /// PTZs send 25 to 60 times per second, so a few missed messages are
+    /// stale
+    fn default() -> Self {
+        Timeouts {
+            stale: Duration::from_millis(100),
+            lost: Duration::from_secs(1),
+        }
+    }
+}
+
+impl Timeouts {
+    pub fn state(&self, age: Option<Duration>) -> TrackingState {
+        match age {
+            Some(age) if age < self.stale => TrackingState::Ok,
+            Some(age) if age < self.lost => TrackingState::Stale,
+            _ => TrackingState::Lost,
+        }
+    }
+}
+
+impl Display for TrackingState {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TrackingState::Ok => write!(f, "ok"),
+            TrackingState::Stale => write!(f, "stale"),
+            TrackingState::Lost => write!(f, "lost"),
+        }
+    }
+}
+
+#[test]
+fn test_state() {
+    let timeouts = Timeouts::default();
+    assert_eq!(timeouts.state(Some(Duration::ZERO)), TrackingState::Ok);
+    assert_eq!(timeouts.state(Some(Duration::from_millis(100))), TrackingState::Stale);
+    assert_eq!(timeouts.state(Some(Duration::from_secs(1))), TrackingState::Lost);
+    assert_eq!(timeouts.state(None), TrackingState::Lost);
+}
@@ -1,5 +1,7 @@
 use std::f32::consts::PI;
 use nalgebra::{Matrix3x4, Matrix4, Point2, Vector3};
+use lvc_camera_overlays::lens::LensProfile;
+use lvc_camera_overlays::ptz::Pose;
 
 // From OpenCV. In pixels
 const CENTER: (f32, f32) = (954.293667, 551.196783);
@@ -20,27 +22,50 @@ const INTRINSIC_PARAMS: Matrix3x4<f32> = Matrix3x4::new(
 /// From OpenCV: k_1, k_2, p_1, p_2, k_3
 const DISTORTION: [f32; 5] = [-0.09120233, 0.10029151, -0.0004659, -0.00094341, -0.05962273];
 
-/// Camera model. Does not by default include support for translations.
-#[derive(Copy, Clone, Debug, Default)]
+/// Camera model.
+///
+/// Points are given in metres, with `x` to the right, `y` down and `z` towards
+/// the camera when it looks straight ahead. The origin is the origin of the
+/// FreeD coordinate system, which is the camera itself if it doesn't report a
+/// position.
+#[derive(Clone, Debug, Default)]
 pub struct Camera {
-    /// Aka camera extrinsic matrix
+    /// Aka camera extrinsic matrix, together with the position
     rotation_matrix: Matrix4<f32>,
+    position: Vector3<f32>,
     focal_length: f32,
+    lens: LensProfile,
 }
 
 impl Camera {
-    pub fn set_rotation(&mut self, yaw: f32, pitch: f32, roll: f32) {
-        // In camera coordinate system, y is up/down and z is the original y.
-        // Therefore, roll and yaw must be switched.
-        self.rotation_matrix = Matrix4::from_euler_angles(yaw * PI / 180.0, pitch * PI / 180.0, roll * PI / 180.0);
+    pub fn new(lens: LensProfile) -> Self {
+        Camera { lens, ..Camera::default() }
     }
 
+    /// Point the camera like `pose`: rotation including roll, position and
+    /// zoom
+    pub fn set_pose(&mut self, pose: &Pose) {
+        self.set_rotation(pose.tilt, pose.pan, pose.roll);
+        self.set_position(pose.position);
+        self.set_zoom(pose.zoom);
+    }
+
+    /// Set the rotation, in degrees
+    pub fn set_rotation(&mut self, tilt: f32, pan: f32, roll: f32) {
+        // In camera coordinate system, tilt turns around x, pan around y (down)
+        // and roll around z
+        self.rotation_matrix = Matrix4::from_euler_angles(tilt * PI / 180.0, pan * PI / 180.0, roll * PI / 180.0);
+    }
+
+    /// Set the position from FreeD, in metres, where X and Y are horizontal,
+    /// Y is straight ahead and Z points up.
+    pub fn set_position(&mut self, (x, y, z): (f32, f32, f32)) {
+        self.position = Vector3::new(x, -z, -y);
+    }
+
+    /// Set the zoom from the raw FreeD value, using the lens profile
     pub fn set_zoom(&mut self, zoom: u32) {
-        self.focal_length = match zoom {
-            0..=4095 => 1.0 + zoom as f32 / 4096.0 * 0.6,
-            4096..=16384 => 1.6 + (zoom - 4096) as f32 / 16384.0 * 5.0,
-            _ => panic!("zoom out of range")
-        }
+        self.focal_length = self.lens.magnification(zoom);
     }
 
     /// Project a vector `[ x y z ]` into camera space coordinates.
@@ -51,7 +76,8 @@ impl Camera {
     pub fn project(&self, point: Vector3<f32>) -> Point2<f32> {
         let zoom_matrix = Matrix4::new(self.focal_length, 0.0, 0.0, 0.0, 0.0, self.focal_length, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 1.0);
         //zoom_matrix.fill_with_identity();
-        let p: Vector3<f32> = INTRINSIC_PARAMS * zoom_matrix * self.rotation_matrix * point.insert_row(3, 1.0);
+        let extrinsic = self.rotation_matrix * Matrix4::new_translation(&-self.position);
+        let p: Vector3<f32> = INTRINSIC_PARAMS * zoom_matrix * extrinsic * point.insert_row(3, 1.0);
         let x = ((p.x / p.z) - CENTER.0) / F.0;
         let y = ((p.y / p.z) - CENTER.1) / F.1;
         let r2 = x * x + y * y;
@@ -63,7 +89,10 @@ impl Camera {
     }
 }
 
+#[cfg(test)]
 mod test {
+    use super::*;
+
     #[test]
     fn test_test() {
         let mut cam = Camera::default();
@@ -71,4 +100,60 @@ mod test {
         println!("{}", cam.project(Vector3::new(1.5, 0.1, -2.0)));
         println!("{}", cam.project(Vector3::new(1.5, 1.0, -2.0)));
     }
+
+    #[test]
+    fn test_position() {
+        let mut cam = Camera::default();
+        cam.set_rotation(10.0, -5.0, 0.0);
+        cam.set_zoom(0);
+        let point = cam.project(Vector3::new(0.2, -0.3, -2.0));
+
+        // Moving the camera 1 m right, 2 m forward and 0.5 m up is the same
+        // as moving the point the other way
+        cam.set_position((1.0, 2.0, 0.5));
+        let moved = cam.project(Vector3::new(1.2, -0.8, -4.0));
+        assert!((point - moved).norm() < 1e-2, "{point} != {moved}");
+    }
+
+    #[test]
+    fn test_pose() {
+        let mut freed = lvc_camera_overlays::freed::FreeD::zero();
+        (freed.tilt, freed.pan, freed.position, freed.zoom) = (10.0, -5.0, (1.0, 2.0, 0.5), 2000);
+        let mut from_pose = Camera::default();
+        from_pose.set_pose(&Pose::new(&freed, std::time::Instant::now()));
+
+        let mut cam = Camera::default();
+        cam.set_rotation(10.0, -5.0, 0.0);
+        cam.set_position((1.0, 2.0, 0.5));
+        cam.set_zoom(2000);
+        let point = Vector3::new(0.2, -0.3, -2.0);
+        assert_eq!(from_pose.project(point), cam.project(point));
+    }
+
+    #[test]
+    fn test_roll() {
+        let mut freed = lvc_camera_overlays::freed::FreeD::zero();
+        (freed.tilt, freed.pan, freed.roll) = (10.0, -5.0, 30.0);
+        let mut from_pose = Camera::default();
+        from_pose.set_pose(&Pose::new(&freed, std::time::Instant::now()));
+
+        let mut cam = Camera::default();
+        cam.set_rotation(10.0, -5.0, 30.0);
+        cam.set_zoom(0);
+        let point = Vector3::new(0.2, -0.3, -2.0);
+        assert_eq!(from_pose.project(point), cam.project(point));
+
+        // Rolling turns the image around its centre
+        let mut level = Camera::default();
+        level.set_rotation(0.0, 0.0, 0.0);
+        level.set_zoom(0);
+        let mut rolled = level.clone();
+        rolled.set_rotation(0.0, 0.0, 30.0);
+        let ahead = Vector3::new(0.0, 0.0, -2.0);
+        assert!((level.project(ahead) - rolled.project(ahead)).norm() < 1e-3);
+        let centre = level.project(ahead);
+        let (a, b) = (level.project(point) - centre, rolled.project(point) - centre);
+        assert!((a.norm() - b.norm()).abs() < 1.0, "{a} vs {b}");
+        assert!((a.angle(&b).to_degrees() - 30.0).abs() < 0.5, "{a} vs {b}");
+    }
 }
@@ -3,6 +3,8 @@ use crate::ndi::{FourCCVideoType, FrameFormatType, VideoData};
 
 pub struct Frame {
     pub video_data: VideoData,
+    /// Multiplies the alpha of everything drawn, to fade the overlay
+    opacity: f32,
 }
 
 impl Frame {
@@ -22,6 +24,7 @@ impl Frame {
                 None,
                 buf.into_boxed_slice(),
             ),
+            opacity: 1.0,
         }
     }
 
@@ -29,11 +32,17 @@ impl Frame {
         unsafe { self.video_data.p_data().write_bytes(0, (self.width() * self.height() * 3) as usize); }
     }
 
+    /// Set the opacity of what is drawn from now on, from 0 to 1
+    pub fn set_opacity(&mut self, opacity: f32) {
+        self.opacity = opacity.clamp(0.0, 1.0);
+    }
+
     pub fn set_pixel(&mut self, x: u32, y: u32, u: u8, v: u8, lum: u8, a: u8) {
         if x >= self.width() || y >= self.height() {
             // println!("out of range");
             return;
         }
+        let a = (a as f32 * self.opacity) as u8;
         let stride= self.video_data.line_stride_in_bytes().unwrap();
         let uyvy_plane_offset = y * stride + (x / 2) * 4;
         let x_offset = (x % 2) * 2;